@@ -2,71 +2,11 @@ use std::{
     error::Error,
     io::{stdout, Write},
     net::{TcpStream, ToSocketAddrs},
-    pin::Pin,
-    task::{Context, Poll},
 };
 
-use futures_io::{AsyncRead, AsyncWrite};
 use http_body_util::BodyExt;
 use hyper::{header, Request};
-use local_runtime::{io::Async, Executor};
-use pin_project_lite::pin_project;
-
-pin_project! {
-    struct HyperIo<T>{
-        #[pin]
-        inner: T
-    }
-}
-
-impl<T> HyperIo<T> {
-    fn new(inner: T) -> Self {
-        Self { inner }
-    }
-}
-
-impl<T: AsyncWrite> hyper::rt::Write for HyperIo<T> {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<Result<usize, std::io::Error>> {
-        self.project().inner.poll_write(cx, buf)
-    }
-
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
-        self.project().inner.poll_flush(cx)
-    }
-
-    fn poll_shutdown(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-    ) -> Poll<Result<(), std::io::Error>> {
-        self.project().inner.poll_close(cx)
-    }
-}
-
-impl<T: AsyncRead> hyper::rt::Read for HyperIo<T> {
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        mut buf: hyper::rt::ReadBufCursor<'_>,
-    ) -> Poll<Result<(), std::io::Error>> {
-        let remaining = unsafe { buf.as_mut() };
-        for b in &mut *remaining {
-            b.write(0);
-        }
-        let remaining = unsafe { std::mem::transmute::<_, &mut [u8]>(remaining) };
-        match self.project().inner.poll_read(cx, remaining) {
-            Poll::Ready(Ok(n)) => {
-                unsafe { buf.advance(n) };
-                Poll::Ready(Ok(()))
-            }
-            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
-            Poll::Pending => Poll::Pending,
-        }
-    }
-}
+use local_runtime::{hyper::HyperIo, io::Async, Executor};
 
 fn main() -> Result<(), Box<dyn Error>> {
     //let _ = env_logger::builder()
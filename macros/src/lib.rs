@@ -0,0 +1,193 @@
+//! Attribute macros for [`local-runtime`](https://docs.rs/local-runtime)
+//!
+//! This crate shouldn't be used directly. Enable the `macros` feature of `local-runtime` and use
+//! `#[local_runtime::main]` and `#[local_runtime::test]` instead.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{
+    parse::Parser, punctuated::Punctuated, spanned::Spanned, Attribute, Expr, ItemFn, Lit, Meta,
+    Token,
+};
+
+#[derive(Default)]
+struct Options {
+    start_paused: bool,
+    seed: Option<Expr>,
+    timeout_ms: Option<Expr>,
+}
+
+impl Options {
+    fn parse(args: TokenStream) -> syn::Result<Self> {
+        let mut opts = Options::default();
+        let metas = Punctuated::<Meta, Token![,]>::parse_terminated.parse(args)?;
+        for meta in metas {
+            let name = meta.path().get_ident().map(|i| i.to_string());
+            match (name.as_deref(), meta) {
+                // `start_paused` on its own is the same as `start_paused = true`
+                (Some("start_paused"), Meta::Path(_)) => opts.start_paused = true,
+                (Some("start_paused"), Meta::NameValue(nv)) => match nv.value {
+                    Expr::Lit(syn::ExprLit {
+                        lit: Lit::Bool(b), ..
+                    }) => opts.start_paused = b.value,
+                    value => {
+                        return Err(syn::Error::new(value.span(), "expected `true` or `false`"))
+                    }
+                },
+                (Some("seed"), Meta::NameValue(nv)) => opts.seed = Some(nv.value),
+                (Some("timeout_ms"), Meta::NameValue(nv)) => opts.timeout_ms = Some(nv.value),
+                (_, meta) => {
+                    return Err(syn::Error::new(
+                        meta.span(),
+                        "unknown option, expected `start_paused`, `seed = <u64>`, or \
+                         `timeout_ms = <u64>`",
+                    ))
+                }
+            }
+        }
+        Ok(opts)
+    }
+}
+
+fn expand(args: TokenStream, item: TokenStream, test_attr: Option<Attribute>) -> TokenStream {
+    let res = Options::parse(args).and_then(|opts| {
+        let func = syn::parse::<ItemFn>(item)?;
+        expand_fn(opts, func, test_attr)
+    });
+    res.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_fn(
+    opts: Options,
+    func: ItemFn,
+    test_attr: Option<Attribute>,
+) -> syn::Result<TokenStream2> {
+    let sig = &func.sig;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            sig.fn_token.span(),
+            "the `async` keyword is missing from the function declaration",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "the function can't be generic",
+        ));
+    }
+    if sig.inputs.len() > 1 {
+        return Err(syn::Error::new(
+            sig.inputs.span(),
+            "the function can only take one argument, which is the `&Executor`",
+        ));
+    }
+
+    // The original function becomes a nested async function, which is called by a synchronous
+    // function with the same signature minus the arguments
+    let inner = ItemFn {
+        attrs: vec![],
+        vis: syn::Visibility::Inherited,
+        ..func.clone()
+    };
+    let name = &sig.ident;
+    let args = (!sig.inputs.is_empty()).then(|| quote!(&ex));
+    let mut outer_sig = sig.clone();
+    outer_sig.asyncness = None;
+    outer_sig.inputs = Punctuated::new();
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+
+    let shuffle = opts
+        .seed
+        .map(|seed| quote_spanned!(seed.span()=> .shuffle(#seed)));
+    let pause = opts
+        .start_paused
+        .then(|| quote!(::local_runtime::time::pause();));
+    let fut = match opts.timeout_ms {
+        Some(ms) => quote! {
+            async {
+                let timeout = ::core::time::Duration::from_millis(#ms);
+                match ::local_runtime::time::timeout(#name(#args), timeout).await {
+                    ::core::result::Result::Ok(out) => out,
+                    ::core::result::Result::Err(_) => {
+                        ::core::panic!("`{}` timed out after {:?}", ::core::stringify!(#name), timeout)
+                    }
+                }
+            }
+        },
+        None => quote!(#name(#args)),
+    };
+
+    Ok(quote! {
+        #test_attr
+        #(#attrs)*
+        #vis #outer_sig {
+            #inner
+            #pause
+            let ex = ::local_runtime::Executor::new() #shuffle;
+            ex.block_on(#fut)
+        }
+    })
+}
+
+/// Run an `async fn main` with `local_runtime::Executor::block_on`
+///
+/// The function may take a single `&Executor` argument, which is the executor running it, for
+/// spawning tasks. The same options as [`macro@test`] are supported.
+///
+/// # Example
+///
+/// ```
+/// use local_runtime::Executor;
+///
+/// #[local_runtime::main]
+/// async fn main(ex: &Executor<'_>) {
+///     let task = ex.spawn(async { 1 + 1 });
+///     assert_eq!(task.await, 2);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
+    expand(args, item, None)
+}
+
+/// Run an async test with `local_runtime::Executor::block_on`
+///
+/// The test function may take a single `&Executor` argument, which is the executor running the
+/// test, for spawning tasks.
+///
+/// Since this macro is exported as `local_runtime::test`, `use local_runtime::*` makes `#[test]`
+/// ambiguous with the builtin attribute. Use the full path, and add
+/// `use std::prelude::v1::test;` next to any glob import of `local_runtime`.
+///
+/// # Options
+///
+/// - `start_paused`: [Pause](https://docs.rs/local-runtime/latest/local_runtime/time/fn.pause.html)
+///   the clock before running the test, so that timers expire instantly once the test has
+///   nothing else to do.
+/// - `seed = <u64>`: Run the executor in shuffle mode with the given seed, which randomizes the
+///   order in which tasks are polled. See `local_runtime::Executor::shuffle`.
+/// - `timeout_ms = <u64>`: Panic if the test doesn't finish within the given number of
+///   milliseconds. With a paused clock, the timeout is measured in paused time.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use local_runtime::{time::sleep, Executor};
+///
+/// #[local_runtime::test(start_paused, seed = 7, timeout_ms = 1000)]
+/// async fn spawn_and_sleep(ex: &Executor<'_>) {
+///     let task = ex.spawn(sleep(Duration::from_millis(500)));
+///     task.await;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    expand(
+        args,
+        item,
+        Some(syn::parse_quote!(#[::core::prelude::v1::test])),
+    )
+}
@@ -0,0 +1,112 @@
+use std::{
+    cell::RefCell,
+    future::pending,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use futures_lite::future::yield_now;
+use local_runtime::{
+    time::{self, sleep},
+    Executor,
+};
+
+// The workaround for glob imports from the docs
+mod glob {
+    use local_runtime::*;
+    use std::prelude::v1::test;
+
+    #[test]
+    fn builtin_test() {
+        assert!(!time::is_paused());
+    }
+
+    #[local_runtime::test]
+    async fn runtime_test() {
+        time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+}
+
+#[local_runtime::test]
+async fn plain() {
+    sleep(Duration::from_millis(1)).await;
+}
+
+#[local_runtime::test]
+async fn result() -> Result<(), std::num::ParseIntError> {
+    let n: u32 = "5".parse()?;
+    assert_eq!(n, 5);
+    Ok(())
+}
+
+#[local_runtime::test]
+async fn executor_arg(ex: &Executor<'_>) {
+    let task = ex.spawn(async { 5 });
+    assert_eq!(task.await, 5);
+}
+
+#[local_runtime::test(start_paused)]
+async fn paused() {
+    assert!(time::is_paused());
+    let start = time::now();
+    let real_start = Instant::now();
+    sleep(Duration::from_secs(60 * 60)).await;
+    assert_eq!(time::now() - start, Duration::from_secs(60 * 60));
+    assert!(real_start.elapsed() < Duration::from_secs(60));
+}
+
+#[local_runtime::test(start_paused = false)]
+async fn not_paused() {
+    assert!(!time::is_paused());
+}
+
+#[local_runtime::test(timeout_ms = 10_000)]
+async fn finishes_before_timeout() {
+    sleep(Duration::from_millis(1)).await;
+}
+
+#[local_runtime::test(start_paused, timeout_ms = 60_000)]
+#[should_panic(expected = "`hangs` timed out after 60s")]
+async fn hangs() {
+    pending::<()>().await;
+}
+
+// Poll order of three tasks that yield once each
+fn poll_order(seed: u64) -> Vec<u32> {
+    let order = Rc::new(RefCell::new(vec![]));
+    let ex = Executor::new().shuffle(seed);
+    ex.block_on(async {
+        let tasks: Vec<_> = (0..3)
+            .map(|i| {
+                let order = order.clone();
+                ex.spawn(async move {
+                    yield_now().await;
+                    order.borrow_mut().push(i);
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await;
+        }
+    });
+    order.take()
+}
+
+#[local_runtime::test(seed = 3)]
+async fn seeded(ex: &Executor<'_>) {
+    let order = Rc::new(RefCell::new(vec![]));
+    let tasks: Vec<_> = (0..3)
+        .map(|i| {
+            let order = order.clone();
+            ex.spawn(async move {
+                yield_now().await;
+                order.borrow_mut().push(i);
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await;
+    }
+    // Same seed, same order
+    assert_eq!(order.take(), poll_order(3));
+}
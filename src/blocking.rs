@@ -0,0 +1,638 @@
+//! Thread pool for blocking operations
+//!
+//! Blocking calls, such as file system access or CPU-heavy computations, freeze every future on
+//! the runtime's thread while they run. [`spawn_blocking`] moves such calls onto a separate pool
+//! of OS threads and returns a future that completes once the call is done.
+//!
+//! # Pool size
+//!
+//! The pool is shared by all threads in the process. It starts out empty and grows lazily
+//! whenever a job is submitted and there aren't enough idle threads to pick it up, up to a
+//! maximum of [`DEFAULT_MAX_THREADS`] threads. Threads that stay idle for longer than
+//! [`DEFAULT_IDLE_TIMEOUT`] are shut down. Both limits can be changed with [`set_max_threads`]
+//! and [`set_idle_timeout`].
+
+use std::{
+    any::Any,
+    collections::VecDeque,
+    future::{poll_fn, Future},
+    io::{self, Read, Seek, SeekFrom, Write},
+    num::NonZero,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{ready, Context, Poll},
+    thread,
+    time::Duration,
+};
+
+use atomic_waker::AtomicWaker;
+
+/// Default maximum number of threads in the blocking pool
+pub const DEFAULT_MAX_THREADS: usize = 512;
+/// Default duration after which idle threads in the blocking pool are shut down
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct PoolState {
+    queue: VecDeque<Job>,
+    idle: usize,
+    threads: usize,
+    max_threads: usize,
+    idle_timeout: Duration,
+}
+
+struct Pool {
+    state: Mutex<PoolState>,
+    cvar: Condvar,
+}
+
+static POOL: Pool = Pool::new();
+
+impl Pool {
+    const fn new() -> Self {
+        Self {
+            state: Mutex::new(PoolState {
+                queue: VecDeque::new(),
+                idle: 0,
+                threads: 0,
+                max_threads: DEFAULT_MAX_THREADS,
+                idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            }),
+            cvar: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        // Jobs never panic while holding the lock, so poisoning can be ignored
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn submit(&'static self, job: Job) {
+        let mut state = self.lock();
+        state.queue.push_back(job);
+        // Only grow the pool if the idle threads can't handle all the queued jobs
+        if state.queue.len() > state.idle && state.threads < state.max_threads {
+            let res = thread::Builder::new()
+                .name("local-runtime-blocking".into())
+                .spawn(move || self.run_worker());
+            match res {
+                Ok(_) => state.threads += 1,
+                // If there are other threads in the pool, then the job will eventually be picked
+                // up, so only give up if the pool is empty
+                Err(err) if state.threads > 0 => {
+                    log::error!("Failed to spawn blocking thread: {err}");
+                }
+                Err(err) => panic!("Failed to spawn blocking thread: {err}"),
+            }
+        }
+        self.cvar.notify_one();
+    }
+
+    fn run_worker(&self) {
+        let mut state = self.lock();
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = self.lock();
+                continue;
+            }
+
+            let idle_timeout = state.idle_timeout;
+            state.idle += 1;
+            let (guard, res) = self
+                .cvar
+                .wait_timeout(state, idle_timeout)
+                .unwrap_or_else(|err| err.into_inner());
+            state = guard;
+            state.idle -= 1;
+
+            if res.timed_out() && state.queue.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+/// Set the maximum number of threads in the blocking pool
+///
+/// If the pool already has more threads than the new maximum, the extra threads are not shut
+/// down until they become idle.
+pub fn set_max_threads(max_threads: NonZero<usize>) {
+    POOL.lock().max_threads = max_threads.get();
+}
+
+/// Set how long a thread in the blocking pool can stay idle before it shuts down
+///
+/// Threads that are currently idle will keep using the old timeout until they pick up their next
+/// job.
+pub fn set_idle_timeout(idle_timeout: Duration) {
+    POOL.lock().idle_timeout = idle_timeout;
+}
+
+struct Shared<T> {
+    value: Mutex<Option<thread::Result<T>>>,
+    waker: AtomicWaker,
+}
+
+/// Future returned by [`spawn_blocking`]
+///
+/// Dropping a `BlockingTask` detaches it, meaning the closure will still run to completion on the
+/// blocking pool, but its output will be discarded.
+#[must_use = "Futures do nothing unless polled"]
+pub struct BlockingTask<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Future for BlockingTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Register the waker before checking the value, so we don't miss a wakeup that happens in
+        // between the two operations
+        self.shared.waker.register(cx.waker());
+        let value = self
+            .shared
+            .value
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        match value {
+            Some(Ok(val)) => Poll::Ready(val),
+            // Propagate panics from the blocking thread to the awaiting task
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Run a blocking closure on the blocking thread pool, returning a future to its output
+///
+/// The closure runs on a separate OS thread, so it won't prevent other futures on the current
+/// thread from making progress. Once the closure returns, the waker of the returned future is
+/// invoked, which notifies the reactor of the runtime thread if it's waiting for events.
+///
+/// If the closure panics, the panic is propagated to the task that awaits the returned future.
+///
+/// # Example
+///
+/// ```
+/// use local_runtime::{block_on, spawn_blocking};
+///
+/// let sum = block_on(async {
+///     spawn_blocking(|| (1..=100u32).sum::<u32>()).await
+/// });
+/// assert_eq!(sum, 5050);
+/// ```
+pub fn spawn_blocking<T, F>(f: F) -> BlockingTask<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_on(&POOL, f)
+}
+
+fn spawn_on<T, F>(pool: &'static Pool, f: F) -> BlockingTask<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        value: Mutex::new(None),
+        waker: AtomicWaker::new(),
+    });
+    let shared_cl = shared.clone();
+    pool.submit(Box::new(move || {
+        let res = panic::catch_unwind(AssertUnwindSafe(f));
+        *shared_cl
+            .value
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(res);
+        shared_cl.waker.wake();
+    }));
+    BlockingTask { shared }
+}
+
+// Default number of bytes read by `Unblock` when filling its buffer
+const DEFAULT_BUF_SIZE: usize = 8 * 1024;
+
+// Result of an operation that `Unblock` ran on the blocking pool
+enum Op {
+    Read(io::Result<Vec<u8>>),
+    Write(io::Result<usize>),
+    Flush(io::Result<()>),
+    Seek(io::Result<u64>),
+    Custom(Box<dyn Any + Send>),
+}
+
+enum State<T> {
+    // The I/O handle is only `None` if a blocking operation panicked and lost it
+    Idle(Option<T>),
+    // Also tracks whether the operation is a read
+    Busy(BlockingTask<(T, Op)>, bool),
+}
+
+/// Async adapter that runs the blocking I/O operations of `T` on the blocking pool
+///
+/// Only one operation can be in flight at a time. The I/O handle is moved into the blocking pool
+/// while the operation runs, and moved back out once the operation completes. If an operation is
+/// abandoned before it completes, its result will be consumed by the next operation.
+///
+/// Reads are buffered, so the handle may have read past the data that has been returned to the
+/// caller. Seekable handles should call `poll_unread` before writing to rewind to the logical
+/// position.
+pub(crate) struct Unblock<T> {
+    state: State<T>,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+}
+
+impl<T: Send + 'static> Unblock<T> {
+    pub(crate) fn new(io: T) -> Self {
+        Self {
+            state: State::Idle(Some(io)),
+            read_buf: vec![],
+            read_pos: 0,
+        }
+    }
+
+    fn buffered(&self) -> &[u8] {
+        &self.read_buf[self.read_pos..]
+    }
+
+    // Wait for the in-flight operation, returning its result
+    fn poll_op(&mut self, cx: &mut Context) -> Poll<Option<Op>> {
+        if let State::Busy(task, _) = &mut self.state {
+            let (io, op) = ready!(Pin::new(task).poll(cx));
+            self.state = State::Idle(Some(io));
+            Poll::Ready(Some(op))
+        } else {
+            Poll::Ready(None)
+        }
+    }
+
+    // Handle the result of an operation that belongs to another kind of request
+    fn absorb(&mut self, op: Op) -> io::Result<()> {
+        match op {
+            Op::Read(res) => {
+                self.read_buf = res?;
+                self.read_pos = 0;
+            }
+            Op::Write(res) => drop(res?),
+            Op::Flush(res) => res?,
+            Op::Seek(res) => drop(res?),
+            Op::Custom(_) => {}
+        }
+        Ok(())
+    }
+
+    fn start<F>(&mut self, reading: bool, f: F)
+    where
+        F: FnOnce(&mut T) -> Op + Send + 'static,
+    {
+        let State::Idle(io) = &mut self.state else {
+            unreachable!("started blocking operation while another is in flight")
+        };
+        let mut io = io
+            .take()
+            .expect("I/O handle was lost due to a panic in a blocking operation");
+        let task = spawn_blocking(move || {
+            let op = f(&mut io);
+            (io, op)
+        });
+        self.state = State::Busy(task, reading);
+    }
+
+    // Fill the read buffer with up to `len` bytes if it's empty
+    fn poll_fill(&mut self, cx: &mut Context, len: usize) -> Poll<io::Result<()>>
+    where
+        T: Read,
+    {
+        loop {
+            if !self.buffered().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            match ready!(self.poll_op(cx)) {
+                // An empty read means EOF
+                Some(Op::Read(res)) => {
+                    self.absorb(Op::Read(res))?;
+                    return Poll::Ready(Ok(()));
+                }
+                Some(op) => self.absorb(op)?,
+                None => {}
+            }
+            if !self.buffered().is_empty() {
+                continue;
+            }
+
+            let mut buf = std::mem::take(&mut self.read_buf);
+            self.read_pos = 0;
+            buf.resize(len, 0);
+            self.start(true, move |io| {
+                Op::Read(io.read(&mut buf).map(|n| {
+                    buf.truncate(n);
+                    buf
+                }))
+            });
+        }
+    }
+
+    pub(crate) fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>>
+    where
+        T: Read,
+    {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        ready!(self.poll_fill(cx, buf.len()))?;
+        let n = self.buffered().len().min(buf.len());
+        buf[..n].copy_from_slice(&self.buffered()[..n]);
+        self.read_pos += n;
+        Poll::Ready(Ok(n))
+    }
+
+    pub(crate) fn poll_fill_buf(&mut self, cx: &mut Context) -> Poll<io::Result<&[u8]>>
+    where
+        T: Read,
+    {
+        ready!(self.poll_fill(cx, DEFAULT_BUF_SIZE))?;
+        Poll::Ready(Ok(self.buffered()))
+    }
+
+    pub(crate) fn consume(&mut self, amt: usize) {
+        self.read_pos = (self.read_pos + amt).min(self.read_buf.len());
+    }
+
+    pub(crate) fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>>
+    where
+        T: Write,
+    {
+        loop {
+            match ready!(self.poll_op(cx)) {
+                Some(Op::Write(res)) => return Poll::Ready(res),
+                Some(op) => self.absorb(op)?,
+                None => {}
+            }
+            let data = buf.to_vec();
+            self.start(false, move |io| Op::Write(io.write(&data)));
+        }
+    }
+
+    pub(crate) fn poll_flush(&mut self, cx: &mut Context) -> Poll<io::Result<()>>
+    where
+        T: Write,
+    {
+        loop {
+            match ready!(self.poll_op(cx)) {
+                Some(Op::Flush(res)) => return Poll::Ready(res),
+                Some(op) => self.absorb(op)?,
+                None => {}
+            }
+            self.start(false, |io| Op::Flush(io.flush()));
+        }
+    }
+
+    pub(crate) fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom) -> Poll<io::Result<u64>>
+    where
+        T: Seek,
+    {
+        loop {
+            match ready!(self.poll_op(cx)) {
+                Some(Op::Seek(res)) => return Poll::Ready(res),
+                Some(op) => self.absorb(op)?,
+                None => {}
+            }
+            // Relative seeks need to account for the data we've read ahead
+            let pos = match pos {
+                SeekFrom::Current(n) => SeekFrom::Current(n - self.buffered().len() as i64),
+                pos => pos,
+            };
+            self.read_buf.clear();
+            self.read_pos = 0;
+            self.start(false, move |io| Op::Seek(io.seek(pos)));
+        }
+    }
+
+    /// Rewind the handle to discard data that has been read ahead but not returned to the caller
+    pub(crate) fn poll_unread(&mut self, cx: &mut Context) -> Poll<io::Result<()>>
+    where
+        T: Seek,
+    {
+        loop {
+            // Only wait for reads, since they add to the read-ahead. Other operations can't be in
+            // flight while there's data that has been read ahead, so their results are left for
+            // the caller to consume.
+            if let State::Busy(_, true) = self.state {
+                if let Some(op) = ready!(self.poll_op(cx)) {
+                    self.absorb(op)?;
+                }
+            }
+            if self.buffered().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            ready!(self.poll_seek(cx, SeekFrom::Current(0)))?;
+        }
+    }
+
+    /// Run a closure on the I/O handle in the blocking pool
+    pub(crate) async fn with_mut<R, F>(&mut self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut T) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let mut f = Some(f);
+        poll_fn(|cx| loop {
+            match ready!(self.poll_op(cx)) {
+                Some(Op::Custom(out)) if f.is_none() => {
+                    return Poll::Ready(Ok(*out.downcast::<R>().unwrap()))
+                }
+                Some(op) => self.absorb(op)?,
+                None => {}
+            }
+            let f = f.take().expect("blocking closure already started");
+            self.start(false, move |io| Op::Custom(Box::new(f(io))));
+        })
+        .await
+    }
+
+    /// Wait for the in-flight operation to complete, then return the I/O handle
+    pub(crate) async fn into_inner(mut self) -> T {
+        poll_fn(|cx| self.poll_op(cx).map(drop)).await;
+        match self.state {
+            State::Idle(io) => {
+                io.expect("I/O handle was lost due to a panic in a blocking operation")
+            }
+            State::Busy(..) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Barrier};
+
+    use crate::{block_on, test::MockWaker};
+
+    use super::*;
+
+    fn new_pool(max_threads: usize, idle_timeout: Duration) -> &'static Pool {
+        let pool = Box::leak(Box::new(Pool::new()));
+        let mut state = pool.lock();
+        state.max_threads = max_threads;
+        state.idle_timeout = idle_timeout;
+        drop(state);
+        pool
+    }
+
+    #[test]
+    fn grow_and_shrink() {
+        let pool = new_pool(3, Duration::from_millis(20));
+        let (tx, rx) = mpsc::channel();
+        let rx = Arc::new(Mutex::new(rx));
+
+        let tasks: Vec<_> = (0..5)
+            .map(|i| {
+                let rx = rx.clone();
+                spawn_on(pool, move || {
+                    rx.lock().unwrap().recv().unwrap();
+                    i
+                })
+            })
+            .collect();
+        // The pool should never grow past its maximum size
+        assert_eq!(pool.lock().threads, 3);
+
+        for _ in 0..5 {
+            tx.send(()).unwrap();
+        }
+        let out = block_on(async {
+            let mut out = vec![];
+            for task in tasks {
+                out.push(task.await);
+            }
+            out
+        });
+        assert_eq!(out, [0, 1, 2, 3, 4]);
+
+        // Idle threads should eventually shut down
+        while pool.lock().threads != 0 {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn reuse_idle_thread() {
+        let pool = new_pool(4, Duration::from_secs(1));
+
+        block_on(spawn_on(pool, || ()));
+        assert_eq!(pool.lock().threads, 1);
+        // Wait until the thread becomes idle
+        while pool.lock().idle == 0 {
+            thread::yield_now();
+        }
+        // The idle thread should pick up the next job instead of a new thread being spawned
+        block_on(spawn_on(pool, || ()));
+        assert_eq!(pool.lock().threads, 1);
+    }
+
+    #[test]
+    fn concurrent_jobs() {
+        // Each job waits for all the others, so this only finishes if they run concurrently
+        let barrier = Arc::new(Barrier::new(4));
+        block_on(async {
+            let tasks: Vec<_> = (0..4)
+                .map(|_| {
+                    let barrier = barrier.clone();
+                    spawn_blocking(move || {
+                        barrier.wait();
+                    })
+                })
+                .collect();
+            for task in tasks {
+                task.await;
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "blocking panic")]
+    fn propagate_panic() {
+        block_on(spawn_blocking(|| panic!("blocking panic")));
+    }
+
+    #[test]
+    fn unblock_read_ahead() {
+        let mut unblock = Unblock::new(io::Cursor::new(b"hello world".to_vec()));
+        block_on(async {
+            let mut buf = [0u8; 5];
+            let n = poll_fn(|cx| unblock.poll_read(cx, &mut buf)).await.unwrap();
+            assert_eq!(&buf[..n], b"hello");
+
+            // Fill the buffer, reading ahead of the caller
+            let data = poll_fn(|cx| unblock.poll_fill_buf(cx).map_ok(|b| b.to_vec()))
+                .await
+                .unwrap();
+            assert_eq!(data, b" world");
+            unblock.consume(1);
+
+            // Relative seeks should be based on the position of the caller, not the read-ahead
+            let pos = poll_fn(|cx| unblock.poll_seek(cx, SeekFrom::Current(0)))
+                .await
+                .unwrap();
+            assert_eq!(pos, 6);
+
+            let mut buf = [0u8; 2];
+            poll_fn(|cx| unblock.poll_read(cx, &mut buf)).await.unwrap();
+            assert_eq!(&buf, b"wo");
+            // Writes after rewinding should happen right after the last byte returned
+            poll_fn(|cx| unblock.poll_unread(cx)).await.unwrap();
+            poll_fn(|cx| unblock.poll_write(cx, b"WO")).await.unwrap();
+            poll_fn(|cx| unblock.poll_flush(cx)).await.unwrap();
+
+            let len = unblock
+                .with_mut(|cursor| cursor.get_ref().len())
+                .await
+                .unwrap();
+            assert_eq!(len, 11);
+            assert_eq!(unblock.into_inner().await.into_inner(), b"hello woWOd");
+        });
+    }
+
+    // Cursor with slow writes, so that writes are always in flight when first polled
+    struct SlowCursor(io::Cursor<Vec<u8>>);
+
+    impl Write for SlowCursor {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            thread::sleep(Duration::from_millis(20));
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for SlowCursor {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn unblock_abandoned_op() {
+        let waker = Arc::new(MockWaker::default()).into();
+        let mut unblock = Unblock::new(SlowCursor(io::Cursor::new(vec![])));
+
+        // Start a write but never complete it
+        assert!(unblock
+            .poll_write(&mut Context::from_waker(&waker), b"abc")
+            .is_pending());
+        // The next operation should wait for the write to finish
+        block_on(poll_fn(|cx| unblock.poll_flush(cx))).unwrap();
+        let pos = block_on(poll_fn(|cx| unblock.poll_seek(cx, SeekFrom::Current(0)))).unwrap();
+        assert_eq!(pos, 3);
+    }
+}
@@ -0,0 +1,353 @@
+//! Cooperative cancellation
+//!
+//! Unlike [`TaskHandle::cancel`](crate::TaskHandle::cancel), which drops a task at its next
+//! await point, a [`CancellationToken`] only signals that work should stop. Tasks observe the
+//! signal and decide how to wind down, such as by finishing their current request first.
+//!
+//! Tokens form a hierarchy. Cancelling a token also cancels all of its
+//! [child tokens](CancellationToken::child_token), but not the other way around.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use pin_project_lite::pin_project;
+use slab::Slab;
+
+#[derive(Default)]
+struct Inner {
+    cancelled: Cell<bool>,
+    // Wakers of the `Cancelled` futures waiting on the token
+    wakers: RefCell<Slab<Waker>>,
+    children: RefCell<Vec<Weak<Inner>>>,
+    // Keeps the parent alive, so that it can still reach the descendants of this token after the
+    // handles to it are dropped
+    _parent: Option<Rc<Inner>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if !self.cancelled.replace(true) {
+            // Move the wakers and children out before cancelling them, in case waking accesses
+            // the token
+            let wakers = std::mem::take(&mut *self.wakers.borrow_mut());
+            for (_, waker) in wakers {
+                waker.wake();
+            }
+            let children = std::mem::take(&mut *self.children.borrow_mut());
+            for child in children.iter().filter_map(Weak::upgrade) {
+                child.cancel();
+            }
+        }
+    }
+}
+
+/// A token for signalling cancellation to tasks on the same thread
+///
+/// Cloning the token creates another handle to the same signal, so cancelling one clone cancels
+/// all of them. Once cancelled, a token stays cancelled.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use local_runtime::{cancel::CancellationToken, time::sleep, Executor};
+///
+/// let token = CancellationToken::new();
+/// let ex = Executor::new();
+/// ex.block_on(async {
+///     let worker_token = token.clone();
+///     let worker = ex.spawn(async move {
+///         let mut count = 0;
+///         while !worker_token.is_cancelled() {
+///             count += 1;
+///             sleep(Duration::from_millis(1)).await;
+///         }
+///         count
+///     });
+///
+///     sleep(Duration::from_millis(10)).await;
+///     token.cancel();
+///     assert!(worker.await > 0);
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Rc<Inner>,
+}
+
+impl Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    /// Create a token that isn't cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a child token, which is cancelled when this token is cancelled
+    ///
+    /// Cancelling the child token doesn't affect this token. If this token is already cancelled,
+    /// the child token starts out cancelled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use local_runtime::cancel::CancellationToken;
+    ///
+    /// let parent = CancellationToken::new();
+    /// let child = parent.child_token();
+    /// let grandchild = child.child_token();
+    ///
+    /// child.cancel();
+    /// assert!(!parent.is_cancelled());
+    /// assert!(grandchild.is_cancelled());
+    ///
+    /// let child2 = parent.child_token();
+    /// parent.cancel();
+    /// assert!(child2.is_cancelled());
+    /// ```
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken {
+            inner: Rc::new(Inner {
+                _parent: Some(self.inner.clone()),
+                ..Default::default()
+            }),
+        };
+        if self.is_cancelled() {
+            child.inner.cancelled.set(true);
+        } else {
+            let mut children = self.inner.children.borrow_mut();
+            // Forget about the children that have been dropped
+            children.retain(|c| c.strong_count() > 0);
+            children.push(Rc::downgrade(&child.inner));
+        }
+        child
+    }
+
+    /// Cancel the token and all of its descendants, waking up all futures waiting on them
+    ///
+    /// Does nothing if the token is already cancelled.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    /// Check if the token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.get()
+    }
+
+    /// Wait until the token is cancelled
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+
+    /// Run a future until it completes or until the token is cancelled
+    ///
+    /// Returns the output of the future, or `None` if the token was cancelled first. If the token
+    /// is already cancelled, the future is never polled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{future::pending, time::Duration};
+    /// use local_runtime::{cancel::CancellationToken, time::sleep, Executor};
+    ///
+    /// let token = CancellationToken::new();
+    /// let ex = Executor::new();
+    /// ex.block_on(async {
+    ///     let child = token.child_token();
+    ///     let task = ex.spawn(async move { child.run_until_cancelled(pending::<()>()).await });
+    ///     sleep(Duration::from_millis(5)).await;
+    ///     token.cancel();
+    ///     assert_eq!(task.await, None);
+    ///
+    ///     let token = CancellationToken::new();
+    ///     assert_eq!(token.run_until_cancelled(async { 5 }).await, Some(5));
+    /// });
+    /// ```
+    pub fn run_until_cancelled<F: Future>(&self, fut: F) -> RunUntilCancelled<'_, F> {
+        RunUntilCancelled {
+            cancelled: self.cancelled(),
+            fut,
+        }
+    }
+}
+
+/// Future returned by [`CancellationToken::cancelled`]
+#[must_use = "Futures do nothing unless polled"]
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+    key: Option<usize>,
+}
+
+impl Debug for Cancelled<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cancelled")
+            .field("token", self.token)
+            .finish()
+    }
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.token.is_cancelled() {
+            // The token has already dropped our waker
+            self.key = None;
+            return Poll::Ready(());
+        }
+
+        let token = self.token;
+        let mut wakers = token.inner.wakers.borrow_mut();
+        match self.key {
+            Some(key) => {
+                let waker = &mut wakers[key];
+                if !waker.will_wake(cx.waker()) {
+                    waker.clone_from(cx.waker());
+                }
+            }
+            None => self.key = Some(wakers.insert(cx.waker().clone())),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.token.inner.wakers.borrow_mut().try_remove(key);
+        }
+    }
+}
+
+pin_project! {
+    /// Future returned by [`CancellationToken::run_until_cancelled`]
+    #[must_use = "Futures do nothing unless polled"]
+    #[derive(Debug)]
+    pub struct RunUntilCancelled<'a, F> {
+        cancelled: Cancelled<'a>,
+        #[pin]
+        fut: F,
+    }
+}
+
+impl<F: Future> Future for RunUntilCancelled<'_, F> {
+    type Output = Option<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        // Check for cancellation first, so that the future isn't polled after cancellation
+        if Pin::new(this.cancelled).poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        this.fut.poll(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::pin, sync::Arc};
+
+    use crate::test::MockWaker;
+
+    use super::*;
+
+    #[test]
+    fn cancelled() {
+        let waker = Arc::new(MockWaker::default());
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        let mut fut1 = pin!(token.cancelled());
+        let mut fut2 = Box::pin(clone.cancelled());
+
+        assert!(fut1
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.clone().into()))
+            .is_pending());
+        assert!(fut2
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.clone().into()))
+            .is_pending());
+        assert_eq!(token.inner.wakers.borrow().len(), 2);
+        // Dropping the future removes its waker
+        drop(fut2);
+        assert_eq!(token.inner.wakers.borrow().len(), 1);
+
+        clone.cancel();
+        assert!(waker.get());
+        assert!(token.is_cancelled());
+        assert!(token.inner.wakers.borrow().is_empty());
+        assert!(fut1
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.clone().into()))
+            .is_ready());
+        // Cancelling again does nothing
+        token.cancel();
+    }
+
+    #[test]
+    fn child_tokens() {
+        let waker = Arc::new(MockWaker::default());
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+        let mut fut = pin!(grandchild.cancelled());
+        assert!(fut
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.clone().into()))
+            .is_pending());
+
+        // Dropped children are cleaned up when new children are added
+        drop(parent.child_token());
+        let _child2 = parent.child_token();
+        assert_eq!(parent.inner.children.borrow().len(), 2);
+
+        parent.cancel();
+        assert!(child.is_cancelled());
+        assert!(grandchild.is_cancelled());
+        assert!(waker.get());
+        assert!(parent.inner.children.borrow().is_empty());
+        assert!(parent.child_token().is_cancelled());
+
+        // Dropping the token in the middle doesn't cut the grandchild off from the parent
+        let parent = CancellationToken::new();
+        let grandchild = parent.child_token().child_token();
+        parent.cancel();
+        assert!(grandchild.is_cancelled());
+    }
+
+    #[test]
+    fn run_until_cancelled() {
+        let waker = Arc::new(MockWaker::default());
+        let token = CancellationToken::new();
+        let mut fut = pin!(token.run_until_cancelled(std::future::ready(1)));
+        assert_eq!(
+            fut.as_mut()
+                .poll(&mut Context::from_waker(&waker.clone().into())),
+            Poll::Ready(Some(1))
+        );
+
+        token.cancel();
+        let mut fut = pin!(token.run_until_cancelled(std::future::ready(1)));
+        assert_eq!(
+            fut.as_mut()
+                .poll(&mut Context::from_waker(&waker.clone().into())),
+            Poll::Ready(None)
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use std::{
+    collections::VecDeque,
+    fmt::Debug,
     future::Future,
+    marker::PhantomData,
     pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -10,6 +13,9 @@ use std::{
 
 use atomic_waker::AtomicWaker;
 use futures_core::Stream;
+use slab::Slab;
+
+use crate::{TaskWaker, WakeQueue};
 
 struct FlagWaker {
     waker: Arc<AtomicWaker>,
@@ -141,6 +147,289 @@ macro_rules! join {
     };
 }
 
+#[doc(hidden)]
+pub trait JoinBranches {
+    type Output;
+    type Error;
+
+    // Returns `Ready(Ok)` once all branches are done, or `Ready(Err)` on the first error
+    fn poll_branches(&mut self, base_waker: &Arc<AtomicWaker>) -> Poll<Result<(), Self::Error>>;
+
+    fn take_output(&mut self) -> Self::Output;
+}
+
+enum BranchState<F, T> {
+    Fut(F),
+    Done(T),
+    Taken,
+}
+
+// One future of `join_tuple!` or `try_join!`, which forms a linked list with the remaining
+// futures. `C` converts the output of the future into a result.
+#[doc(hidden)]
+pub struct JoinBranch<F, T, C, R> {
+    state: BranchState<F, T>,
+    convert: C,
+    waker: Option<(Arc<FlagWaker>, Waker)>,
+    rest: R,
+}
+
+impl<F, R> JoinBranch<F, F::Output, fn(F::Output) -> Result<F::Output, R::Error>, R>
+where
+    F: Future + Unpin,
+    R: JoinBranches,
+{
+    pub fn new(fut: F, rest: R) -> Self {
+        Self {
+            state: BranchState::Fut(fut),
+            convert: Ok,
+            waker: None,
+            rest,
+        }
+    }
+}
+
+impl<F, T, R> JoinBranch<F, T, fn(Result<T, R::Error>) -> Result<T, R::Error>, R>
+where
+    F: Future<Output = Result<T, R::Error>> + Unpin,
+    R: JoinBranches,
+{
+    pub fn new_try(fut: F, rest: R) -> Self {
+        Self {
+            state: BranchState::Fut(fut),
+            convert: |res| res,
+            waker: None,
+            rest,
+        }
+    }
+}
+
+impl<F, T, C, R> JoinBranches for JoinBranch<F, T, C, R>
+where
+    F: Future + Unpin,
+    C: FnMut(F::Output) -> Result<T, R::Error>,
+    R: JoinBranches,
+{
+    type Output = (T, R::Output);
+    type Error = R::Error;
+
+    fn poll_branches(&mut self, base_waker: &Arc<AtomicWaker>) -> Poll<Result<(), Self::Error>> {
+        let mut done = true;
+        if let BranchState::Fut(fut) = &mut self.state {
+            let (waker_data, waker) = self
+                .waker
+                .get_or_insert_with(|| FlagWaker::waker_pair(base_waker.clone()));
+            done = false;
+            if waker_data.check_awoken() {
+                if let Poll::Ready(out) = Pin::new(fut).poll(&mut Context::from_waker(waker)) {
+                    self.state = BranchState::Done((self.convert)(out)?);
+                    done = true;
+                }
+            }
+        }
+        match self.rest.poll_branches(base_waker) {
+            Poll::Ready(Ok(())) if done => Poll::Ready(Ok(())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            _ => Poll::Pending,
+        }
+    }
+
+    fn take_output(&mut self) -> Self::Output {
+        match std::mem::replace(&mut self.state, BranchState::Taken) {
+            BranchState::Done(out) => (out, self.rest.take_output()),
+            _ => panic!("expected joined future to be done"),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct JoinEnd<E>(PhantomData<E>);
+
+impl<E> JoinEnd<E> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E> JoinBranches for JoinEnd<E> {
+    type Output = ();
+    type Error = E;
+
+    fn poll_branches(&mut self, _base_waker: &Arc<AtomicWaker>) -> Poll<Result<(), E>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn take_output(&mut self) -> Self::Output {}
+}
+
+// Converts the nested output of the join branches into a flat tuple
+#[doc(hidden)]
+pub trait FlattenTuple {
+    type Flat;
+
+    fn flatten(self) -> Self::Flat;
+}
+
+macro_rules! nested_tuple {
+    () => { () };
+    ($t:ident $($rest:ident)*) => { ($t, nested_tuple!($($rest)*)) };
+}
+
+macro_rules! impl_flatten_tuple {
+    ($($t:ident)+) => {
+        impl<$($t),+> FlattenTuple for nested_tuple!($($t)+) {
+            type Flat = ($($t,)+);
+
+            #[allow(non_snake_case)]
+            fn flatten(self) -> Self::Flat {
+                let nested_tuple!($($t)+) = self;
+                ($($t,)+)
+            }
+        }
+    };
+}
+
+impl_flatten_tuple!(A);
+impl_flatten_tuple!(A B);
+impl_flatten_tuple!(A B C);
+impl_flatten_tuple!(A B C D);
+impl_flatten_tuple!(A B C D E);
+impl_flatten_tuple!(A B C D E F);
+impl_flatten_tuple!(A B C D E F G);
+impl_flatten_tuple!(A B C D E F G H);
+impl_flatten_tuple!(A B C D E F G H I);
+impl_flatten_tuple!(A B C D E F G H I J);
+impl_flatten_tuple!(A B C D E F G H I J K);
+impl_flatten_tuple!(A B C D E F G H I J K L);
+
+#[doc(hidden)]
+#[must_use = "Futures do nothing unless polled"]
+pub struct JoinTupleFuture<B> {
+    base_waker: Arc<AtomicWaker>,
+    branches: B,
+}
+
+// The outputs are never pinned
+impl<B> Unpin for JoinTupleFuture<B> {}
+
+impl<B: JoinBranches> JoinTupleFuture<B> {
+    pub fn new(branches: B) -> Self {
+        Self {
+            base_waker: Arc::new(AtomicWaker::new()),
+            branches,
+        }
+    }
+}
+
+impl<B> Future for JoinTupleFuture<B>
+where
+    B: JoinBranches,
+    B::Output: FlattenTuple,
+{
+    type Output = Result<<B::Output as FlattenTuple>::Flat, B::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.base_waker.register(cx.waker());
+        this.branches
+            .poll_branches(&this.base_waker)
+            .map_ok(|()| this.branches.take_output().flatten())
+    }
+}
+
+/// Poll multiple futures concurrently, returning a future that outputs a tuple of all results
+/// once all futures have completed.
+///
+/// Unlike [`join`], the futures can have different output types, which don't need to be `Unpin`.
+/// Up to 12 futures are supported.
+///
+/// # Minimal polling
+///
+/// This future will only poll each inner future when it is awoken, rather than polling all inner
+/// futures on each iteration.
+///
+/// # Examples
+///
+/// ```
+/// use local_runtime::join_tuple;
+///
+/// # local_runtime::block_on(async {
+/// let a = async { 1 };
+/// let b = async { "two" };
+/// let c = async { Some(3.0) };
+/// assert_eq!(join_tuple!(a, b, c).await, (1, "two", Some(3.0)));
+/// # })
+/// ```
+#[macro_export]
+macro_rules! join_tuple {
+    ($($fut:expr),+ $(,)?) => {
+        async {
+            match $crate::JoinTupleFuture::new($crate::__join_branches!(new $($fut,)+)).await {
+                Ok(out) => out,
+                Err(err) => {
+                    let err: ::std::convert::Infallible = err;
+                    match err {}
+                }
+            }
+        }
+    };
+}
+
+/// Poll multiple fallible futures concurrently, returning a future that outputs a tuple of all
+/// successful results once all futures have completed.
+///
+/// If any future returns an error, the future returned by this macro immediately completes with
+/// that error, and the remaining futures are dropped. All futures must return a `Result` with
+/// the same error type. Up to 12 futures are supported.
+///
+/// # Minimal polling
+///
+/// This future will only poll each inner future when it is awoken, rather than polling all inner
+/// futures on each iteration.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use local_runtime::{try_join, time::sleep};
+///
+/// # local_runtime::block_on(async {
+/// let a = async { Ok::<_, &str>(1) };
+/// let b = async { Ok("two") };
+/// assert_eq!(try_join!(a, b).await, Ok((1, "two")));
+///
+/// let slow = async {
+///     sleep(Duration::from_secs(10)).await;
+///     Ok(1)
+/// };
+/// let fail = async { Err::<(), _>("error") };
+/// assert_eq!(try_join!(slow, fail).await, Err("error"));
+/// # })
+/// ```
+#[macro_export]
+macro_rules! try_join {
+    ($($fut:expr),+ $(,)?) => {
+        async {
+            $crate::JoinTupleFuture::new($crate::__join_branches!(new_try $($fut,)+)).await
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __join_branches {
+    ($new:ident) => {
+        $crate::JoinEnd::new()
+    };
+    ($new:ident $fut:expr, $($rest:expr,)*) => {
+        $crate::JoinBranch::$new(
+            ::std::pin::pin!($fut),
+            $crate::__join_branches!($new $($rest,)*),
+        )
+    };
+}
+
 #[doc(hidden)]
 #[must_use = "Streams do nothing unless polled"]
 pub struct MergeFutureStream<'a, T, const N: usize> {
@@ -358,8 +647,591 @@ macro_rules! merge_streams {
     };
 }
 
+#[doc(hidden)]
+pub enum SelectOutput<A, B> {
+    This(A),
+    Next(B),
+}
+
+// Terminates the list of select branches, and is also the output when all branches are disabled
+#[doc(hidden)]
+pub struct SelectEnd;
+
+#[doc(hidden)]
+pub trait SelectBranches {
+    type Output;
+
+    fn poll_branches(&mut self, base_waker: &Arc<AtomicWaker>) -> Poll<Self::Output>;
+
+    fn all_disabled(&self) -> bool;
+
+    fn disabled() -> Self::Output;
+}
+
+// One branch of `select!`, which forms a linked list with the remaining branches
+#[doc(hidden)]
+pub struct SelectBranch<F, M, R> {
+    // `None` means the branch is disabled
+    fut: Option<F>,
+    matches: M,
+    waker: Option<(Arc<FlagWaker>, Waker)>,
+    rest: R,
+}
+
+impl<F, M, R> SelectBranch<F, M, R>
+where
+    F: Future + Unpin,
+    M: FnMut(F::Output) -> Option<F::Output>,
+{
+    pub fn new(fut: F, enabled: bool, matches: M, rest: R) -> Self {
+        Self {
+            fut: enabled.then_some(fut),
+            matches,
+            waker: None,
+            rest,
+        }
+    }
+}
+
+impl<F, M, R> SelectBranches for SelectBranch<F, M, R>
+where
+    F: Future + Unpin,
+    M: FnMut(F::Output) -> Option<F::Output>,
+    R: SelectBranches,
+{
+    type Output = SelectOutput<F::Output, R::Output>;
+
+    fn poll_branches(&mut self, base_waker: &Arc<AtomicWaker>) -> Poll<Self::Output> {
+        if let Some(fut) = &mut self.fut {
+            let (waker_data, waker) = self
+                .waker
+                .get_or_insert_with(|| FlagWaker::waker_pair(base_waker.clone()));
+            if waker_data.check_awoken() {
+                if let Poll::Ready(out) = Pin::new(fut).poll(&mut Context::from_waker(waker)) {
+                    // Whether or not the output matches the pattern, the branch is now done
+                    self.fut = None;
+                    if let Some(out) = (self.matches)(out) {
+                        return Poll::Ready(SelectOutput::This(out));
+                    }
+                }
+            }
+        }
+        self.rest.poll_branches(base_waker).map(SelectOutput::Next)
+    }
+
+    fn all_disabled(&self) -> bool {
+        self.fut.is_none() && self.rest.all_disabled()
+    }
+
+    fn disabled() -> Self::Output {
+        SelectOutput::Next(R::disabled())
+    }
+}
+
+impl SelectBranches for SelectEnd {
+    type Output = SelectEnd;
+
+    fn poll_branches(&mut self, _base_waker: &Arc<AtomicWaker>) -> Poll<Self::Output> {
+        Poll::Pending
+    }
+
+    fn all_disabled(&self) -> bool {
+        true
+    }
+
+    fn disabled() -> Self::Output {
+        SelectEnd
+    }
+}
+
+#[doc(hidden)]
+#[must_use = "Futures do nothing unless polled"]
+pub struct SelectFuture<B> {
+    base_waker: Arc<AtomicWaker>,
+    branches: B,
+}
+
+impl<B: SelectBranches> SelectFuture<B> {
+    pub fn new(branches: B) -> Self {
+        Self {
+            base_waker: Arc::new(AtomicWaker::new()),
+            branches,
+        }
+    }
+}
+
+impl<B: SelectBranches + Unpin> Future for SelectFuture<B> {
+    type Output = B::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.base_waker.register(cx.waker());
+        if let Poll::Ready(out) = this.branches.poll_branches(&this.base_waker) {
+            return Poll::Ready(out);
+        }
+        if this.branches.all_disabled() {
+            Poll::Ready(B::disabled())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Wait on multiple futures concurrently, running the handler of the first future that completes.
+///
+/// Each branch has the form `<pattern> (if <guard>)? = <future> (, if <precondition>)? =>
+/// <handler>`. Once a future completes, its output is matched against the pattern and guard of its
+/// branch. If the output matches, the other futures are dropped, and then the handler is run with
+/// the bindings of the pattern. Otherwise, the branch is disabled and the remaining futures
+/// continue to run.
+///
+/// Branches whose precondition is false are disabled from the start, although their future
+/// expressions are still evaluated. If all branches are disabled, the `else` branch is run. If
+/// there is no `else` branch, the macro panics.
+///
+/// The output of `select!` is the output of the handler that was run, so all handlers must
+/// evaluate to the same type. Since the handlers run in the enclosing scope, they can use `break`,
+/// `continue`, `return`, and `?`.
+///
+/// # Minimal polling
+///
+/// The futures are only polled when they are awoken, rather than on every iteration. Futures are
+/// polled in order, so earlier branches have priority if multiple futures complete at once.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use local_runtime::{select, time::sleep};
+///
+/// # local_runtime::block_on(async {
+/// let out = select! {
+///     _ = sleep(Duration::from_millis(50)) => "slow",
+///     n = async { 5 } => if n == 5 { "fast" } else { "wrong" },
+/// };
+/// assert_eq!(out, "fast");
+/// # })
+/// ```
+///
+/// Branches can be disabled with refutable patterns, guards, or preconditions.
+///
+/// ```
+/// use local_runtime::select;
+///
+/// # local_runtime::block_on(async {
+/// let out = select! {
+///     Some(x) = async { None::<u8> } => x,
+///     n if n > 5 = async { 3 } => n,
+///     _ = async { 1 }, if false => 1,
+///     else => 0,
+/// };
+/// assert_eq!(out, 0);
+/// # })
+/// ```
+#[macro_export]
+macro_rules! select {
+    ($($tokens:tt)*) => {
+        $crate::__select!(@parse [] [] $($tokens)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select {
+    // Parse the branches into the form `($pat) ($guard) ($fut) ($cond) ({ $handler })`
+    // Block handlers have separate rules with and without a trailing comma, since `$(,)?`
+    // followed by more tokens is ambiguous
+    (@parse [$($branches:tt)*] [] else => $handler:block, $($rest:tt)*) => {
+        $crate::__select!(@parse [$($branches)*] [$handler] $($rest)*)
+    };
+    (@parse [$($branches:tt)*] [] else => $handler:block $($rest:tt)*) => {
+        $crate::__select!(@parse [$($branches)*] [$handler] $($rest)*)
+    };
+    (@parse [$($branches:tt)*] [] else => $handler:expr $(, $($rest:tt)*)?) => {
+        $crate::__select!(@parse [$($branches)*] [{ $handler }] $($($rest)*)?)
+    };
+    (@parse [$($branches:tt)*] [$($else:tt)?] else $($rest:tt)*) => {
+        compile_error!("select! can only have one else branch")
+    };
+    (@parse [$($branches:tt)*] $else:tt $pat:pat if $($rest:tt)*) => {
+        $crate::__select!(@guard [$($branches)*] $else ($pat) [] $($rest)*)
+    };
+    (@parse [$($branches:tt)*] $else:tt $pat:pat = $($rest:tt)*) => {
+        $crate::__select!(@future [$($branches)* ($pat) (true)] $else $($rest)*)
+    };
+    (@parse [$($branches:tt)*] $else:tt) => {{
+        // The futures are dropped at the end of this statement, before any handler runs
+        let out = $crate::SelectFuture::new($crate::__select!(@branches $($branches)*)).await;
+        $crate::__select!(@handle out $else $($branches)*)
+    }};
+
+    // Collect the tokens of the guard up to the `=`, since an `expr` can't be followed by `=`
+    (@guard [$($branches:tt)*] $else:tt $pat:tt [$($guard:tt)+] = $($rest:tt)*) => {
+        $crate::__select!(@future [$($branches)* $pat ($($guard)+)] $else $($rest)*)
+    };
+    (@guard [$($branches:tt)*] $else:tt $pat:tt [$($guard:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__select!(@guard [$($branches)*] $else $pat [$($guard)* $next] $($rest)*)
+    };
+
+    // Parse the rest of a branch after the `=`
+    (@future [$($branches:tt)*] $else:tt
+        $fut:expr, if $cond:expr => $handler:block, $($rest:tt)*
+    ) => {
+        $crate::__select!(@parse [$($branches)* ($fut) ($cond) ($handler)] $else $($rest)*)
+    };
+    (@future [$($branches:tt)*] $else:tt
+        $fut:expr, if $cond:expr => $handler:block $($rest:tt)*
+    ) => {
+        $crate::__select!(@parse [$($branches)* ($fut) ($cond) ($handler)] $else $($rest)*)
+    };
+    (@future [$($branches:tt)*] $else:tt
+        $fut:expr, if $cond:expr => $handler:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select!(@parse [$($branches)* ($fut) ($cond) ({ $handler })] $else $($($rest)*)?)
+    };
+    (@future [$($branches:tt)*] $else:tt $fut:expr => $handler:block, $($rest:tt)*) => {
+        $crate::__select!(@parse [$($branches)* ($fut) (true) ($handler)] $else $($rest)*)
+    };
+    (@future [$($branches:tt)*] $else:tt $fut:expr => $handler:block $($rest:tt)*) => {
+        $crate::__select!(@parse [$($branches)* ($fut) (true) ($handler)] $else $($rest)*)
+    };
+    (@future [$($branches:tt)*] $else:tt $fut:expr => $handler:expr $(, $($rest:tt)*)?) => {
+        $crate::__select!(@parse [$($branches)* ($fut) (true) ({ $handler })] $else $($($rest)*)?)
+    };
+
+    // Build the linked list of branches
+    (@branches) => { $crate::SelectEnd };
+    (@branches
+        ($pat:pat) ($guard:expr) ($fut:expr) ($cond:expr) ($handler:tt) $($rest:tt)*
+    ) => {
+        $crate::SelectBranch::new(
+            ::std::pin::pin!($fut),
+            $cond,
+            |out| {
+                let mut matches = false;
+                // The guard always fails, so the arm is never taken and the output isn't moved.
+                // This gives the guard the same bindings as in a regular match.
+                #[allow(unused_variables, unreachable_code, unreachable_patterns)]
+                match out {
+                    $pat if {
+                        matches = $guard;
+                        false
+                    } => unreachable!(),
+                    _ => {}
+                }
+                matches.then_some(out)
+            },
+            $crate::__select!(@branches $($rest)*),
+        )
+    };
+
+    // Match the output against each branch in turn
+    (@handle $out:ident []) => {{
+        let $crate::SelectEnd = $out;
+        panic!("all branches are disabled and there is no else branch")
+    }};
+    (@handle $out:ident [$else:tt]) => {{
+        let $crate::SelectEnd = $out;
+        $else
+    }};
+    (@handle $out:ident $else:tt
+        ($pat:pat) ($guard:expr) ($fut:expr) ($cond:expr) ($handler:tt) $($rest:tt)*
+    ) => {
+        match $out {
+            $crate::SelectOutput::This($pat) => $handler,
+            #[allow(unreachable_patterns)]
+            $crate::SelectOutput::This(_) => unreachable!("select! output didn't match pattern"),
+            $crate::SelectOutput::Next($out) => $crate::__select!(@handle $out $else $($rest)*),
+        }
+    };
+}
+
+type Entry<T> = (Pin<Box<T>>, (Arc<TaskWaker>, Waker));
+
+// Dynamically-sized set of pinned futures or streams, each with their own waker
+struct Unordered<T> {
+    entries: Slab<Entry<T>>,
+    wake_queue: Arc<WakeQueue>,
+    // IDs that have been taken from the wake queue but haven't been polled yet
+    ready: VecDeque<usize>,
+    // The wake queue can only be drained on the thread that created it
+    _phantom: PhantomData<*const ()>,
+}
+
+impl<T> Unordered<T> {
+    fn new() -> Self {
+        Self {
+            entries: Slab::new(),
+            wake_queue: Arc::new(WakeQueue::with_capacity(0)),
+            ready: VecDeque::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        let entry = self.entries.vacant_entry();
+        let waker_pair = TaskWaker::waker_pair(self.wake_queue.clone(), entry.key());
+        // Make sure the new entry gets polled, even if the set is being polled elsewhere
+        waker_pair.1.wake_by_ref();
+        entry.insert((Box::pin(item), waker_pair));
+    }
+
+    // Poll the awoken entries until one of them yields an item. The handler returns the item, if
+    // any, along with whether the entry should be kept.
+    fn poll_entries<O, I>(
+        &mut self,
+        cx: &mut Context,
+        mut poll: impl FnMut(Pin<&mut T>, &mut Context) -> Poll<O>,
+        mut handle: impl FnMut(O) -> (Option<I>, bool),
+    ) -> Poll<Option<I>> {
+        self.wake_queue.base_waker.register(cx.waker());
+        let ready = &mut self.ready;
+        self.wake_queue.drain_for_each(|id| ready.push_back(id));
+
+        while let Some(id) = self.ready.pop_front() {
+            // If the waker outlives its entry, the ID may belong to another entry, which only
+            // causes a spurious poll
+            let Some((item, (waker_data, waker))) = self.entries.get_mut(id) else {
+                continue;
+            };
+            // Reset the waker so that it can produce wakeups again
+            waker_data.to_sleep();
+            if let Poll::Ready(out) = poll(item.as_mut(), &mut Context::from_waker(waker)) {
+                let (out, keep) = handle(out);
+                if keep {
+                    // Poll this entry again next time
+                    waker.wake_by_ref();
+                } else {
+                    self.entries.remove(id);
+                }
+                if out.is_some() {
+                    return Poll::Ready(out);
+                }
+            }
+        }
+
+        if self.entries.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// A dynamically-sized set of futures that are polled concurrently
+///
+/// This is a [`Stream`] that yields the outputs of its futures in the order in which they
+/// complete. Futures can be pushed into the set at any time, even after it has started being
+/// polled. The stream ends once the set is empty.
+///
+/// Unlike [`Executor`](crate::Executor), the futures are driven by polling the set itself, so
+/// they can borrow from the surrounding scope and don't need to be spawned. The set is `!Send`.
+///
+/// # Minimal polling
+///
+/// Each future has its own waker, so only the futures that have been awoken are polled, rather
+/// than polling every future on each iteration.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use futures_lite::StreamExt;
+/// use local_runtime::{time::sleep, FuturesUnordered};
+///
+/// async fn delay(n: u64) -> u64 {
+///     sleep(Duration::from_millis(n)).await;
+///     n
+/// }
+///
+/// # local_runtime::block_on(async {
+/// let mut futures: FuturesUnordered<_> = [30, 10, 20].into_iter().map(delay).collect();
+/// assert_eq!(futures.next().await, Some(10));
+/// futures.push(delay(5));
+/// assert_eq!(futures.collect::<Vec<_>>().await, [5, 20, 30]);
+/// # })
+/// ```
+#[must_use = "Streams do nothing unless polled"]
+pub struct FuturesUnordered<F> {
+    inner: Unordered<F>,
+}
+
+impl<F> Default for FuturesUnordered<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> Debug for FuturesUnordered<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FuturesUnordered")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<F> FuturesUnordered<F> {
+    /// Create an empty set of futures
+    pub fn new() -> Self {
+        Self {
+            inner: Unordered::new(),
+        }
+    }
+
+    /// Add a future to the set
+    ///
+    /// The future will be polled the next time the set is polled.
+    pub fn push(&mut self, fut: F) {
+        self.inner.push(fut);
+    }
+
+    /// Number of futures in the set that haven't completed
+    pub fn len(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    /// Whether the set has no futures
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.is_empty()
+    }
+}
+
+impl<F: Future> Stream for FuturesUnordered<F> {
+    type Item = F::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .inner
+            .poll_entries(cx, |fut, cx| fut.poll(cx), |out| (Some(out), false))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl<F> FromIterator<F> for FuturesUnordered<F> {
+    fn from_iter<I: IntoIterator<Item = F>>(iter: I) -> Self {
+        let mut this = Self::new();
+        this.extend(iter);
+        this
+    }
+}
+
+impl<F> Extend<F> for FuturesUnordered<F> {
+    fn extend<I: IntoIterator<Item = F>>(&mut self, iter: I) {
+        for fut in iter {
+            self.push(fut);
+        }
+    }
+}
+
+/// A dynamically-sized set of streams that are polled concurrently
+///
+/// This is a [`Stream`] that yields the items of its inner streams as they become available,
+/// similar to [`merge_streams`](crate::merge_streams). Streams can be pushed into the set at any
+/// time, and are removed once they end. The stream ends once the set is empty.
+///
+/// # Minimal polling
+///
+/// Each stream has its own waker, so only the streams that have been awoken are polled, rather
+/// than polling every stream on each iteration.
+///
+/// # Examples
+///
+/// ```
+/// use futures_lite::{stream, StreamExt};
+/// use local_runtime::SelectAll;
+///
+/// # local_runtime::block_on(async {
+/// let mut streams = SelectAll::new();
+/// streams.push(stream::iter(vec![1, 2]));
+/// streams.push(stream::iter(vec![3]));
+/// let mut items = streams.collect::<Vec<_>>().await;
+/// items.sort();
+/// assert_eq!(items, [1, 2, 3]);
+/// # })
+/// ```
+#[must_use = "Streams do nothing unless polled"]
+pub struct SelectAll<S> {
+    inner: Unordered<S>,
+}
+
+impl<S> Default for SelectAll<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Debug for SelectAll<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectAll")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<S> SelectAll<S> {
+    /// Create an empty set of streams
+    pub fn new() -> Self {
+        Self {
+            inner: Unordered::new(),
+        }
+    }
+
+    /// Add a stream to the set
+    ///
+    /// The stream will be polled the next time the set is polled.
+    pub fn push(&mut self, stream: S) {
+        self.inner.push(stream);
+    }
+
+    /// Number of streams in the set that haven't ended
+    pub fn len(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    /// Whether the set has no streams
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.is_empty()
+    }
+}
+
+impl<S: Stream> Stream for SelectAll<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().inner.poll_entries(
+            cx,
+            |stream, cx| stream.poll_next(cx),
+            |item| {
+                let keep = item.is_some();
+                (item, keep)
+            },
+        )
+    }
+}
+
+impl<S> FromIterator<S> for SelectAll<S> {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut this = Self::new();
+        this.extend(iter);
+        this
+    }
+}
+
+impl<S> Extend<S> for SelectAll<S> {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, iter: I) {
+        for stream in iter {
+            self.push(stream);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{cell::Cell, future::poll_fn, task::Waker};
+
     use crate::test::MockWaker;
 
     use super::*;
@@ -386,4 +1258,73 @@ mod tests {
         flag_waker.wake_by_ref();
         assert!(wk2.get());
     }
+
+    #[test]
+    fn futures_unordered() {
+        let waker = Arc::new(MockWaker::default());
+        let polls = Cell::new(0);
+        let stored = Cell::new(None::<Waker>);
+        let mut futures = FuturesUnordered::new();
+        let task_waker = waker.clone().into();
+        let mut cx = Context::from_waker(&task_waker);
+
+        // Empty set ends immediately
+        assert_eq!(Pin::new(&mut futures).poll_next(&mut cx), Poll::Ready(None));
+
+        futures.push(Box::pin(poll_fn(|cx| {
+            polls.set(polls.get() + 1);
+            if polls.get() == 1 {
+                stored.set(Some(cx.waker().clone()));
+                Poll::Pending
+            } else {
+                Poll::Ready(1)
+            }
+        })) as Pin<Box<dyn Future<Output = i32>>>);
+        futures.push(Box::pin(std::future::pending()));
+        assert!(waker.get());
+        assert_eq!(futures.len(), 2);
+        assert!(Pin::new(&mut futures).poll_next(&mut cx).is_pending());
+        assert_eq!(polls.get(), 1);
+
+        // Not awoken, so nothing gets polled
+        waker.set(false);
+        assert!(Pin::new(&mut futures).poll_next(&mut cx).is_pending());
+        assert_eq!(polls.get(), 1);
+
+        // Pushing while the set is pending should wake it up
+        futures.push(Box::pin(async { 2 }));
+        assert!(waker.get());
+        assert_eq!(
+            Pin::new(&mut futures).poll_next(&mut cx),
+            Poll::Ready(Some(2))
+        );
+
+        stored.take().unwrap().wake();
+        assert_eq!(
+            Pin::new(&mut futures).poll_next(&mut cx),
+            Poll::Ready(Some(1))
+        );
+        assert_eq!(polls.get(), 2);
+        assert_eq!(futures.len(), 1);
+        assert!(Pin::new(&mut futures).poll_next(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn select_all() {
+        let waker = Arc::new(MockWaker::default());
+        let task_waker = waker.clone().into();
+        let mut cx = Context::from_waker(&task_waker);
+        let mut streams: SelectAll<_> = [vec![1, 2], vec![3]]
+            .into_iter()
+            .map(futures_lite::stream::iter)
+            .collect();
+
+        let mut items = vec![];
+        while let Poll::Ready(Some(item)) = Pin::new(&mut streams).poll_next(&mut cx) {
+            items.push(item);
+        }
+        items.sort();
+        assert_eq!(items, [1, 2, 3]);
+        assert!(streams.is_empty());
+    }
 }
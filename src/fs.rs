@@ -0,0 +1,293 @@
+//! Async filesystem operations
+//!
+//! File systems don't support non-blocking I/O, so every operation in this module runs on the
+//! [blocking thread pool](crate::blocking). The returned futures complete on the current thread
+//! once the operation is done, so other futures can keep running in the meantime.
+//!
+//! See [`File`] for reading and writing files.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, DirEntry, Metadata},
+    future::Future,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use futures_io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+use crate::blocking::{spawn_blocking, BlockingTask, Unblock};
+
+/// Read the entire contents of a file into a bytes vector
+///
+/// This is the async version of [`std::fs::read`].
+pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move || fs::read(path)).await
+}
+
+/// Read the entire contents of a file into a string
+///
+/// This is the async version of [`std::fs::read_to_string`].
+pub async fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move || fs::read_to_string(path)).await
+}
+
+/// Write a slice as the entire contents of a file
+///
+/// This is the async version of [`std::fs::write`].
+pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    let contents = contents.as_ref().to_owned();
+    spawn_blocking(move || fs::write(path, contents)).await
+}
+
+/// Query the metadata of a file or directory
+///
+/// This is the async version of [`std::fs::metadata`].
+pub async fn metadata(path: impl AsRef<Path>) -> io::Result<Metadata> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move || fs::metadata(path)).await
+}
+
+/// Rename a file or directory, replacing the destination if it already exists
+///
+/// This is the async version of [`std::fs::rename`].
+pub async fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let from = from.as_ref().to_owned();
+    let to = to.as_ref().to_owned();
+    spawn_blocking(move || fs::rename(from, to)).await
+}
+
+/// Remove a file
+///
+/// This is the async version of [`std::fs::remove_file`].
+pub async fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move || fs::remove_file(path)).await
+}
+
+/// Return a stream over the entries of a directory
+///
+/// This is the async version of [`std::fs::read_dir`].
+///
+/// # Example
+///
+/// ```no_run
+/// use futures_lite::StreamExt;
+/// use local_runtime::fs;
+///
+/// # local_runtime::block_on(async {
+/// let mut entries = fs::read_dir(".").await?;
+/// while let Some(entry) = entries.next().await {
+///     println!("{}", entry?.path().display());
+/// }
+/// # Ok::<_, std::io::Error>(())
+/// # });
+/// ```
+pub async fn read_dir(path: impl AsRef<Path>) -> io::Result<ReadDir> {
+    let path = path.as_ref().to_owned();
+    let inner = spawn_blocking(move || fs::read_dir(path)).await?;
+    Ok(ReadDir {
+        state: ReadDirState::Idle(Some(inner)),
+        entries: VecDeque::new(),
+    })
+}
+
+// Number of directory entries read in each blocking operation
+const READ_DIR_BATCH: usize = 32;
+
+type ReadDirBatch = (fs::ReadDir, VecDeque<io::Result<DirEntry>>);
+
+enum ReadDirState {
+    // `None` means the directory has been exhausted
+    Idle(Option<fs::ReadDir>),
+    Busy(BlockingTask<ReadDirBatch>),
+}
+
+/// Stream of entries in a directory
+///
+/// This is created by [`read_dir`].
+#[must_use = "Streams do nothing unless polled"]
+pub struct ReadDir {
+    state: ReadDirState,
+    entries: VecDeque<io::Result<DirEntry>>,
+}
+
+impl Stream for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(entry) = this.entries.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+            match &mut this.state {
+                ReadDirState::Idle(None) => return Poll::Ready(None),
+                ReadDirState::Idle(inner) => {
+                    let mut inner = inner.take().unwrap();
+                    this.state = ReadDirState::Busy(spawn_blocking(move || {
+                        let entries = inner.by_ref().take(READ_DIR_BATCH).collect();
+                        (inner, entries)
+                    }));
+                }
+                ReadDirState::Busy(task) => {
+                    let (inner, entries) = ready!(Pin::new(task).poll(cx));
+                    // A partial batch means we've hit the end of the directory
+                    let done = entries.len() < READ_DIR_BATCH;
+                    this.state = ReadDirState::Idle((!done).then_some(inner));
+                    this.entries = entries;
+                }
+            }
+        }
+    }
+}
+
+/// An open file on the filesystem
+///
+/// Implements [`AsyncRead`], [`AsyncBufRead`], [`AsyncWrite`], and [`AsyncSeek`]. Each I/O operation runs on the
+/// [blocking thread pool](crate::blocking), and only one operation can be in flight at a time.
+///
+/// Reads are buffered, so the underlying file may be positioned past the data that has been
+/// returned. The file is rewound to the correct position before each write and seek, so this is
+/// only observable when accessing the file through another handle.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures_lite::{AsyncReadExt, AsyncWriteExt};
+/// use local_runtime::fs::File;
+///
+/// # local_runtime::block_on(async {
+/// let mut file = File::create("hello.txt").await?;
+/// file.write_all(b"hello world").await?;
+/// file.flush().await?;
+///
+/// let mut file = File::open("hello.txt").await?;
+/// let mut contents = String::new();
+/// file.read_to_string(&mut contents).await?;
+/// assert_eq!(contents, "hello world");
+/// # Ok::<_, std::io::Error>(())
+/// # });
+/// ```
+pub struct File {
+    inner: Unblock<fs::File>,
+}
+
+impl File {
+    /// Open a file in read-only mode
+    ///
+    /// This is the async version of [`std::fs::File::open`].
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<File> {
+        let path: PathBuf = path.as_ref().to_owned();
+        let file = spawn_blocking(move || fs::File::open(path)).await?;
+        Ok(Self::from_std(file))
+    }
+
+    /// Open a file in write-only mode, creating it if it doesn't exist and truncating it if it does
+    ///
+    /// This is the async version of [`std::fs::File::create`].
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<File> {
+        let path: PathBuf = path.as_ref().to_owned();
+        let file = spawn_blocking(move || fs::File::create(path)).await?;
+        Ok(Self::from_std(file))
+    }
+
+    /// Wrap a standard file handle
+    pub fn from_std(file: fs::File) -> File {
+        Self {
+            inner: Unblock::new(file),
+        }
+    }
+
+    /// Wait for any in-flight operation to complete, then return the standard file handle
+    ///
+    /// Data that has been read ahead but not returned is discarded, so the position of the
+    /// returned file may be past the position of this handle.
+    pub async fn into_std(self) -> fs::File {
+        self.inner.into_inner().await
+    }
+
+    /// Query the metadata of the file
+    pub async fn metadata(&mut self) -> io::Result<Metadata> {
+        self.inner.with_mut(|file| file.metadata()).await?
+    }
+
+    /// Truncate or extend the file to `size` bytes
+    pub async fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.inner.with_mut(move |file| file.set_len(size)).await?
+    }
+
+    /// Synchronize all data and metadata of the file to the filesystem
+    pub async fn sync_all(&mut self) -> io::Result<()> {
+        self.inner.with_mut(|file| file.sync_all()).await?
+    }
+
+    /// Synchronize the data of the file to the filesystem, without necessarily synchronizing
+    /// metadata
+    pub async fn sync_data(&mut self) -> io::Result<()> {
+        self.inner.with_mut(|file| file.sync_data()).await?
+    }
+}
+
+impl From<fs::File> for File {
+    fn from(file: fs::File) -> Self {
+        Self::from_std(file)
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().inner.poll_read(cx, buf)
+    }
+}
+
+impl AsyncBufRead for File {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().inner.poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().inner.consume(amt);
+    }
+}
+
+impl AsyncWrite for File {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Make sure we write at the position the caller expects
+        ready!(this.inner.poll_unread(cx))?;
+        this.inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for File {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        self.get_mut().inner.poll_seek(cx, pos)
+    }
+}
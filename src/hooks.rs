@@ -0,0 +1,93 @@
+//! Instrumentation hooks for the runtime lifecycle
+//!
+//! Park hooks run when the current thread's reactor is about to block waiting for events, and
+//! after it wakes up. They're configured per thread with [`set_park_hooks`].
+//!
+//! Task hooks run before and after each task on an executor is polled. They're configured per
+//! executor with [`Executor::before_task_poll`] and [`Executor::after_task_poll`].
+//!
+//! [`Executor::before_task_poll`]: crate::Executor::before_task_poll
+//! [`Executor::after_task_poll`]: crate::Executor::after_task_poll
+
+use std::rc::Rc;
+
+use crate::{reactor::REACTOR, task::TaskId};
+
+/// Callbacks that run when the current thread parks and unparks
+///
+/// The reactor parks the thread when it waits for I/O events or timers with a non-zero timeout.
+/// If the reactor knows that it will wake up immediately, it doesn't park, so no hooks run.
+///
+/// The hooks may freely use the runtime, such as spawning tasks or registering timers. If a hook
+/// wakes up a task, then the reactor wakes up right after parking.
+#[derive(Default, Clone)]
+pub struct ParkHooks {
+    before_park: Option<Rc<dyn Fn()>>,
+    after_unpark: Option<Rc<dyn Fn()>>,
+}
+
+impl ParkHooks {
+    /// Create a set of hooks that do nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a callback that runs right before the thread parks
+    pub fn before_park(mut self, f: impl Fn() + 'static) -> Self {
+        self.before_park = Some(Rc::new(f));
+        self
+    }
+
+    /// Set a callback that runs right after the thread unparks
+    pub fn after_unpark(mut self, f: impl Fn() + 'static) -> Self {
+        self.after_unpark = Some(Rc::new(f));
+        self
+    }
+
+    pub(crate) fn run_before_park(&self) {
+        if let Some(f) = &self.before_park {
+            f();
+        }
+    }
+
+    pub(crate) fn run_after_unpark(&self) {
+        if let Some(f) = &self.after_unpark {
+            f();
+        }
+    }
+}
+
+impl std::fmt::Debug for ParkHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParkHooks")
+            .field("before_park", &self.before_park.is_some())
+            .field("after_unpark", &self.after_unpark.is_some())
+            .finish()
+    }
+}
+
+/// Set the park hooks of the current thread, returning the previous hooks
+///
+/// # Example
+///
+/// ```
+/// use std::{cell::Cell, rc::Rc, time::Duration};
+/// use local_runtime::{block_on, hooks::{set_park_hooks, ParkHooks}, time::sleep};
+///
+/// let parks = Rc::new(Cell::new(0));
+/// let parks_clone = parks.clone();
+/// set_park_hooks(ParkHooks::new().before_park(move || parks_clone.set(parks_clone.get() + 1)));
+///
+/// block_on(sleep(Duration::from_millis(10)));
+/// assert!(parks.get() > 0);
+/// ```
+pub fn set_park_hooks(hooks: ParkHooks) -> ParkHooks {
+    REACTOR.with(|r| r.set_park_hooks(hooks))
+}
+
+// Task poll hooks of an executor
+#[derive(Default)]
+pub(crate) struct TaskHooks<'a> {
+    pub(crate) before_poll: Option<Box<dyn Fn(TaskId) + 'a>>,
+    pub(crate) after_poll: Option<Box<dyn Fn(TaskId) + 'a>>,
+}
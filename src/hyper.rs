@@ -0,0 +1,237 @@
+//! Integration with the [`hyper`](https://docs.rs/hyper) HTTP library
+//!
+//! Hyper is runtime-agnostic, so it needs to be told how to do I/O, spawn tasks, and set timers
+//! through the traits in [`hyper::rt`]. This module implements those traits:
+//!
+//! - [`HyperIo`] adapts any [`AsyncRead`]/[`AsyncWrite`] type, such as [`Async`](crate::Async),
+//!   into [`hyper::rt::Read`] and [`hyper::rt::Write`].
+//! - [`HyperExecutor`] implements [`hyper::rt::Executor`], so hyper can spawn the background
+//!   tasks for its HTTP/2 connections on the current executor.
+//! - [`HyperTimer`] implements [`hyper::rt::Timer`] with [`Timer`], so hyper's timeouts respect
+//!   [paused time](crate::time::pause).
+//!
+//! This module requires the `hyper` feature. The HTTP versions, client, and server need to be
+//! enabled through the features of `hyper` itself.
+//!
+//! # Example
+//!
+//! Serve HTTP/1 requests over TCP.
+//!
+//! ```no_run
+//! use std::{convert::Infallible, net::TcpListener};
+//! use hyper::{server::conn::http1, service::service_fn, Request, Response};
+//! use local_runtime::{hyper::{HyperIo, HyperTimer}, io::Async, Executor};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let ex = Executor::new();
+//! ex.block_on(async {
+//!     let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 8080))?;
+//!     loop {
+//!         let (stream, _) = listener.accept().await?;
+//!         let service = service_fn(|_req: Request<hyper::body::Incoming>| async {
+//!             Ok::<_, Infallible>(Response::new(String::from("hello")))
+//!         });
+//!         let conn = http1::Builder::new()
+//!             .timer(HyperTimer)
+//!             .serve_connection(HyperIo::new(stream), service);
+//!         ex.spawn(async move {
+//!             if let Err(err) = conn.await {
+//!                 eprintln!("Connection failed: {err}");
+//!             }
+//!         });
+//!     }
+//!     Ok::<_, std::io::Error>(())
+//! })
+//! # }
+//! ```
+
+use std::{
+    future::Future,
+    io,
+    mem::{ManuallyDrop, MaybeUninit},
+    pin::Pin,
+    task::{ready, Context, Poll},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+use pin_project_lite::pin_project;
+
+use crate::{spawn_local, time::Timer};
+
+pin_project! {
+    /// Adapter that implements hyper's I/O traits for [`AsyncRead`] and [`AsyncWrite`] types
+    ///
+    /// Works with any `futures_io` stream, such as [`Async`](crate::Async) or
+    /// [`DuplexStream`](crate::io::DuplexStream).
+    #[derive(Debug)]
+    pub struct HyperIo<T> {
+        #[pin]
+        inner: T,
+    }
+}
+
+impl<T> HyperIo<T> {
+    /// Wrap an I/O stream
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get reference to inner stream
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable reference to inner stream
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the inner stream
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead> ::hyper::rt::Read for HyperIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: ::hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        // SAFETY: We don't de-initialize any part of the buffer
+        let uninit = unsafe { buf.as_mut() };
+        // AsyncRead can only read into initialized buffers
+        for byte in uninit.iter_mut() {
+            byte.write(0);
+        }
+        let len = uninit.len();
+        // SAFETY: All bytes of the buffer have just been initialized, and MaybeUninit<u8> has the
+        // same layout as u8
+        let init = unsafe { &mut *(uninit as *mut [MaybeUninit<u8>] as *mut [u8]) };
+        let n = ready!(self.project().inner.poll_read(cx, init))?;
+        assert!(n <= len, "reader returned more bytes than the buffer size");
+        // SAFETY: The first n bytes of the buffer are initialized and contain the read data
+        unsafe { buf.advance(n) };
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite> ::hyper::rt::Write for HyperIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+}
+
+/// Implementation of [`hyper::rt::Executor`] that spawns onto the current executor
+///
+/// Tasks are spawned and detached with [`spawn_local`], so they must be `'static`. Hyper's
+/// HTTP/2 tasks keep copies of their executor, which rules out spawning with a reference to an
+/// [`Executor`](crate::Executor).
+///
+/// # Panic
+///
+/// Spawning panics if no executor is running on the current thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HyperExecutor;
+
+impl<Fut> ::hyper::rt::Executor<Fut> for HyperExecutor
+where
+    Fut: Future + 'static,
+    Fut::Output: 'static,
+{
+    fn execute(&self, fut: Fut) {
+        spawn_local(fut);
+    }
+}
+
+/// Implementation of [`hyper::rt::Timer`] that uses the runtime's timers
+///
+/// Since the timers rely on the thread-local reactor, the sleep futures created by this timer
+/// must be polled on the thread that created them, which is always the case for connections
+/// driven by [`Executor`](crate::Executor).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HyperTimer;
+
+impl ::hyper::rt::Timer for HyperTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn ::hyper::rt::Sleep>> {
+        Box::pin(HyperSleep::new(Timer::delay(duration)))
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn ::hyper::rt::Sleep>> {
+        Box::pin(HyperSleep::new(Timer::at(deadline)))
+    }
+
+    fn now(&self) -> Instant {
+        crate::time::now()
+    }
+}
+
+// Hyper requires its sleep futures to be Send, but our timers are bound to the thread-local
+// reactor, so check that the timer is only polled or dropped on its original thread
+struct HyperSleep {
+    timer: ManuallyDrop<Timer>,
+    thread: ThreadId,
+}
+
+// SAFETY: Timer is only !Send because its registration lives in the thread-local reactor. It
+// doesn't hold any thread-local pointers, so moving it is memory-safe. Polling it on another
+// thread panics, and dropping it on another thread leaves the registration alone instead of
+// cancelling it in the wrong reactor.
+unsafe impl Send for HyperSleep {}
+
+impl HyperSleep {
+    fn new(timer: Timer) -> Self {
+        Self {
+            timer: ManuallyDrop::new(timer),
+            thread: thread::current().id(),
+        }
+    }
+}
+
+impl Drop for HyperSleep {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // SAFETY: The timer is never used again
+            unsafe { ManuallyDrop::drop(&mut self.timer) };
+        }
+        // On other threads, the timer is leaked. Its registration stays in the original reactor
+        // until it expires, which only wakes a stale waker.
+    }
+}
+
+impl Future for HyperSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert_eq!(
+            thread::current().id(),
+            self.thread,
+            "hyper timer polled on a different thread from where it was created"
+        );
+        Pin::new(&mut *self.timer).poll(cx).map(|_| ())
+    }
+}
+
+impl ::hyper::rt::Sleep for HyperSleep {}
@@ -1,6 +1,14 @@
 //! Async I/O primitives
 //!
-//! See [`Async`] for more details.
+//! See [`Async`] for more details. For async access to the standard I/O streams, see [`stdin`],
+//! [`stdout`], and [`stderr`].
+//!
+//! For testing code that does I/O without real sockets, see [`duplex`] and [`MockStream`].
+
+mod duplex;
+mod mock;
+#[cfg(unix)]
+mod stdio;
 
 #[cfg(unix)]
 use std::os::{
@@ -11,8 +19,8 @@ use std::{
     fs::File,
     future::poll_fn,
     io::{
-        self, BufRead, BufReader, BufWriter, ErrorKind, LineWriter, Read, Stderr, StderrLock,
-        Stdin, StdinLock, Stdout, StdoutLock, Write,
+        self, BufRead, BufReader, BufWriter, ErrorKind, LineWriter, Read, StderrLock, StdinLock,
+        StdoutLock, Write,
     },
     marker::PhantomData,
     net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
@@ -29,6 +37,11 @@ use crate::{
     REACTOR,
 };
 
+pub use duplex::{duplex, DuplexStream};
+pub use mock::{MockBuilder, MockStream};
+#[cfg(unix)]
+pub use stdio::{stderr, stdin, stdout, Stderr, Stdin, Stdout};
+
 /// Types whose I/O trait implementations do not move or drop the underlying I/O object
 ///
 /// The I/O object inside [`Async`] cannot be closed before the [`Async`] is dropped, because
@@ -54,9 +67,9 @@ use crate::{
 pub unsafe trait IoSafe {}
 
 unsafe impl IoSafe for File {}
-unsafe impl IoSafe for Stderr {}
-unsafe impl IoSafe for Stdin {}
-unsafe impl IoSafe for Stdout {}
+unsafe impl IoSafe for io::Stderr {}
+unsafe impl IoSafe for io::Stdin {}
+unsafe impl IoSafe for io::Stdout {}
 unsafe impl IoSafe for StderrLock<'_> {}
 unsafe impl IoSafe for StdinLock<'_> {}
 unsafe impl IoSafe for StdoutLock<'_> {}
@@ -95,8 +108,10 @@ impl Drop for GuardedSource {
 /// # Supported types
 ///
 /// [`Async`] supports any type that implements `AsFd` or `AsSocket`. This includes all standard
-/// networking types. However, `Async` should not be used with types like [`File`] or [`Stdin`],
-/// because they don't work well in non-blocking mode.
+/// networking types. However, `Async` should not be used with types like [`File`] or [`io::Stdin`],
+/// because they don't work well in non-blocking mode. For async file access, see
+/// [`fs::File`](crate::fs::File), and for async standard I/O, see [`stdin`], [`stdout`], and
+/// [`stderr`].
 ///
 /// # Concurrency
 ///
@@ -466,6 +481,42 @@ impl Async<TcpListener> {
         Async::new(TcpListener::bind(addr.into())?)
     }
 
+    /// Create a TCP listener bound to a specific address with `SO_REUSEPORT` set
+    ///
+    /// Multiple listeners with `SO_REUSEPORT` can bind to the same address, in which case the
+    /// kernel distributes incoming connections between them. This allows each thread of a
+    /// [`Runtime`](crate::runtime::Runtime) to accept connections on the same port with its own
+    /// listener.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::TcpListener;
+    /// use local_runtime::io::Async;
+    ///
+    /// let listener1 = Async::<TcpListener>::bind_reuseport(([127, 0, 0, 1], 8000))?;
+    /// let listener2 = Async::<TcpListener>::bind_reuseport(([127, 0, 0, 1], 8000))?;
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_reuseport<A: Into<SocketAddr>>(addr: A) -> io::Result<Self> {
+        use rustix::net::{sockopt, AddressFamily, SocketFlags, SocketType};
+
+        let addr = addr.into();
+        let family = match addr {
+            SocketAddr::V4(_) => AddressFamily::INET,
+            SocketAddr::V6(_) => AddressFamily::INET6,
+        };
+        let socket =
+            rustix::net::socket_with(family, SocketType::STREAM, SocketFlags::CLOEXEC, None)?;
+        sockopt::set_socket_reuseaddr(&socket, true)?;
+        sockopt::set_socket_reuseport(&socket, true)?;
+        rustix::net::bind(&socket, &addr)?;
+        // Same backlog as the standard library
+        rustix::net::listen(&socket, 128)?;
+        Async::new(TcpListener::from(socket))
+    }
+
     fn poll_accept(&self, cx: &mut Context) -> Poll<io::Result<(Async<TcpStream>, SocketAddr)>> {
         // Safety: accept() is I/O safe
         unsafe {
@@ -515,7 +566,7 @@ impl Async<TcpListener> {
     /// # Ok::<_, std::io::Error>(())
     /// # });
     /// ```
-    pub fn incoming(&self) -> IncomingTcp {
+    pub fn incoming(&self) -> IncomingTcp<'_> {
         IncomingTcp { listener: self }
     }
 }
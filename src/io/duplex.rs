@@ -0,0 +1,348 @@
+//! In-memory duplex streams
+
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+};
+
+use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+// One direction of a duplex pair
+struct Pipe {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    // Bytes taken out of `buf` by the reader's `poll_fill_buf` that haven't been consumed yet.
+    // They still count towards the capacity.
+    taken: usize,
+    // Set when the writing half is closed or dropped
+    write_closed: bool,
+    // Set when the reading half is dropped
+    read_closed: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity),
+            capacity,
+            taken: 0,
+            write_closed: false,
+            read_closed: false,
+            read_waker: None,
+            write_waker: None,
+        }
+    }
+
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_writer(&mut self) {
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn lock(pipe: &Mutex<Pipe>) -> MutexGuard<'_, Pipe> {
+    pipe.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// One end of an in-memory duplex stream, created by [`duplex`]
+///
+/// Bytes written to one end can be read from the other end.
+pub struct DuplexStream {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+    // Bytes that have been taken out of the read pipe by `poll_fill_buf`, but not consumed yet
+    read_buf: Vec<u8>,
+    read_pos: usize,
+}
+
+impl std::fmt::Debug for DuplexStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DuplexStream").finish_non_exhaustive()
+    }
+}
+
+/// Create a pair of connected in-memory streams, useful for testing code that uses sockets
+///
+/// Each direction of the stream buffers up to `capacity` bytes. Once the buffer is full,
+/// writes wait until the other end reads some of the data, which applies backpressure to the
+/// writer. Closing or dropping one end causes reads on the other end to return EOF once the
+/// buffered data is consumed. Dropping one end causes writes on the other end to fail with
+/// [`BrokenPipe`](io::ErrorKind::BrokenPipe).
+///
+/// Unlike [`Async`](super::Async), the streams don't use the reactor, and can be used from any
+/// thread or runtime.
+///
+/// # Panic
+///
+/// Panics if `capacity` is 0.
+///
+/// # Example
+///
+/// ```
+/// use futures_lite::{AsyncReadExt, AsyncWriteExt};
+/// use local_runtime::{io::duplex, Executor};
+///
+/// let (mut client, mut server) = duplex(4);
+/// let ex = Executor::new();
+/// ex.block_on(async {
+///     // The writer waits for the reader when the buffer is full
+///     let writer = ex.spawn(async move {
+///         client.write_all(b"hello world").await.unwrap();
+///     });
+///     let mut buf = vec![];
+///     server.read_to_end(&mut buf).await.unwrap();
+///     assert_eq!(buf, b"hello world");
+///     writer.await;
+/// });
+/// ```
+pub fn duplex(capacity: usize) -> (DuplexStream, DuplexStream) {
+    assert!(capacity > 0, "duplex capacity must be non-zero");
+    let a = Arc::new(Mutex::new(Pipe::new(capacity)));
+    let b = Arc::new(Mutex::new(Pipe::new(capacity)));
+    (
+        DuplexStream::new(a.clone(), b.clone()),
+        DuplexStream::new(b, a),
+    )
+}
+
+impl DuplexStream {
+    fn new(read: Arc<Mutex<Pipe>>, write: Arc<Mutex<Pipe>>) -> Self {
+        Self {
+            read,
+            write,
+            read_buf: vec![],
+            read_pos: 0,
+        }
+    }
+
+    fn buffered(&self) -> &[u8] {
+        &self.read_buf[self.read_pos..]
+    }
+
+    // Consume bytes from `read_buf`, which frees up space in the pipe for the writer
+    fn consume_buffered(&mut self, amt: usize) {
+        let amt = amt.min(self.read_buf.len() - self.read_pos);
+        if amt > 0 {
+            self.read_pos += amt;
+            let mut pipe = lock(&self.read);
+            pipe.taken -= amt;
+            pipe.wake_writer();
+        }
+    }
+}
+
+impl AsyncRead for DuplexStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Drain the bytes left over from poll_fill_buf first
+        let buffered = this.buffered();
+        if !buffered.is_empty() {
+            let n = buf.len().min(buffered.len());
+            buf[..n].copy_from_slice(&buffered[..n]);
+            this.consume_buffered(n);
+            return Poll::Ready(Ok(n));
+        }
+
+        let mut pipe = lock(&this.read);
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if pipe.buf.is_empty() {
+            if pipe.write_closed {
+                return Poll::Ready(Ok(0));
+            }
+            pipe.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(pipe.buf.len());
+        for (dst, src) in buf.iter_mut().zip(pipe.buf.drain(..n)) {
+            *dst = src;
+        }
+        pipe.wake_writer();
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncBufRead for DuplexStream {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.buffered().is_empty() {
+            let mut pipe = lock(&this.read);
+            if pipe.buf.is_empty() {
+                if !pipe.write_closed {
+                    pipe.read_waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            } else {
+                this.read_buf.clear();
+                this.read_buf.extend(pipe.buf.drain(..));
+                this.read_pos = 0;
+                pipe.taken = this.read_buf.len();
+            }
+        }
+        Poll::Ready(Ok(this.buffered()))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume_buffered(amt);
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = lock(&self.write);
+        if pipe.read_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if pipe.write_closed {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "write to closed duplex stream",
+            )));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let space = pipe.capacity - pipe.buf.len() - pipe.taken;
+        if space == 0 {
+            pipe.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(space);
+        pipe.buf.extend(&buf[..n]);
+        pipe.wake_reader();
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut pipe = lock(&self.write);
+        pipe.write_closed = true;
+        pipe.wake_reader();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        let mut pipe = lock(&self.write);
+        pipe.write_closed = true;
+        pipe.wake_reader();
+        drop(pipe);
+
+        let mut pipe = lock(&self.read);
+        pipe.read_closed = true;
+        pipe.wake_writer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::pin, sync::Arc};
+
+    use crate::test::MockWaker;
+
+    use super::*;
+
+    #[test]
+    fn backpressure() {
+        let waker = Arc::new(MockWaker::default());
+        let std_waker = waker.clone().into();
+        let cx = &mut Context::from_waker(&std_waker);
+        let (mut a, mut b) = duplex(4);
+
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(cx, b"hello"),
+            Poll::Ready(Ok(4))
+        ));
+        // Buffer is full, so the writer has to wait
+        assert!(Pin::new(&mut a).poll_write(cx, b"o").is_pending());
+        assert!(!waker.get());
+
+        let mut buf = [0; 2];
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(cx, &mut buf),
+            Poll::Ready(Ok(2))
+        ));
+        assert_eq!(&buf, b"he");
+        // Reading frees up space, so the writer is awoken
+        assert!(waker.get());
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(cx, b"o"),
+            Poll::Ready(Ok(1))
+        ));
+
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(cx, b"x"),
+            Poll::Ready(Ok(1))
+        ));
+
+        let mut b = pin!(b);
+        waker.set(false);
+        match b.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(data)) => assert_eq!(data, b"llox"),
+            _ => panic!("expected data"),
+        }
+        // Unconsumed bytes from poll_fill_buf still take up space
+        assert!(!waker.get());
+        assert!(Pin::new(&mut a).poll_write(cx, b"y").is_pending());
+        b.as_mut().consume(1);
+        assert!(waker.get());
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(cx, b"yz"),
+            Poll::Ready(Ok(1))
+        ));
+        assert!(matches!(
+            b.as_mut().poll_read(cx, &mut buf),
+            Poll::Ready(Ok(2))
+        ));
+        assert_eq!(&buf, b"lo");
+        assert!(matches!(
+            b.as_mut().poll_read(cx, &mut buf),
+            Poll::Ready(Ok(1))
+        ));
+        assert_eq!(&buf[..1], b"x");
+        assert!(matches!(
+            b.as_mut().poll_read(cx, &mut buf),
+            Poll::Ready(Ok(1))
+        ));
+        assert_eq!(&buf[..1], b"y");
+
+        // Reader waits for data, and gets EOF once the writer is dropped
+        waker.set(false);
+        assert!(b.as_mut().poll_read(cx, &mut buf).is_pending());
+        drop(a);
+        assert!(waker.get());
+        assert!(matches!(
+            b.as_mut().poll_read(cx, &mut buf),
+            Poll::Ready(Ok(0))
+        ));
+        // Writing to a dropped reader fails
+        assert!(matches!(
+            b.as_mut().poll_write(cx, b"x"),
+            Poll::Ready(Err(err)) if err.kind() == io::ErrorKind::BrokenPipe
+        ));
+    }
+}
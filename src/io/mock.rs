@@ -0,0 +1,333 @@
+//! Scripted mock I/O streams
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::time::Timer;
+
+#[derive(Debug)]
+enum Action {
+    Read(Vec<u8>),
+    Write(Vec<u8>),
+    ReadError(io::Error),
+    WriteError(io::Error),
+    Wait(Duration),
+}
+
+/// Builds a [`MockStream`] from a script of I/O actions
+///
+/// The actions are performed in the order that they're added. See [`MockStream`] for details.
+#[derive(Debug, Default)]
+pub struct MockBuilder {
+    actions: VecDeque<Action>,
+}
+
+impl MockBuilder {
+    /// Create a builder with an empty script
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect the stream to be read, returning `data`
+    ///
+    /// The data may be returned over multiple reads if the read buffer is too small.
+    pub fn read(mut self, data: &[u8]) -> Self {
+        self.actions.push_back(Action::Read(data.to_vec()));
+        self
+    }
+
+    /// Expect `data` to be written to the stream
+    ///
+    /// The data may be written over multiple writes.
+    pub fn write(mut self, data: &[u8]) -> Self {
+        self.actions.push_back(Action::Write(data.to_vec()));
+        self
+    }
+
+    /// Expect the stream to be read, returning `err`
+    pub fn read_error(mut self, err: io::Error) -> Self {
+        self.actions.push_back(Action::ReadError(err));
+        self
+    }
+
+    /// Expect the stream to be written to, returning `err`
+    pub fn write_error(mut self, err: io::Error) -> Self {
+        self.actions.push_back(Action::WriteError(err));
+        self
+    }
+
+    /// Block both reads and writes for `duration`
+    ///
+    /// This uses the runtime's timers, so the stream has to be driven by
+    /// [`block_on`](crate::block_on).
+    pub fn wait(mut self, duration: Duration) -> Self {
+        self.actions.push_back(Action::Wait(duration));
+        self
+    }
+
+    /// Create the mock stream
+    pub fn build(self) -> MockStream {
+        MockStream {
+            actions: self.actions,
+            timer: None,
+            read_waker: None,
+            write_waker: None,
+        }
+    }
+}
+
+/// A mock I/O stream that follows a script of reads, writes, errors, and waits
+///
+/// Reads return the data from the script, and writes are checked against the data in the script.
+/// If a read happens while the script expects a write, or vice versa, the operation waits until
+/// the other side catches up, so the reads and writes can come from different tasks. Once the
+/// script is finished, reads return EOF.
+///
+/// The stream panics if the wrong data is written, if it's written to after the script is
+/// finished, or if it's dropped before the script is finished. This makes it suitable for
+/// checking protocol implementations in tests.
+///
+/// # Example
+///
+/// ```
+/// use std::{io, time::Duration};
+/// use futures_lite::{AsyncReadExt, AsyncWriteExt};
+/// use local_runtime::{block_on, io::MockStream};
+///
+/// let mut stream = MockStream::builder()
+///     .write(b"ping")
+///     .wait(Duration::from_millis(5))
+///     .read(b"pong")
+///     .read_error(io::ErrorKind::ConnectionReset.into())
+///     .build();
+/// block_on(async {
+///     stream.write_all(b"ping").await.unwrap();
+///     let mut buf = [0; 4];
+///     stream.read_exact(&mut buf).await.unwrap();
+///     assert_eq!(&buf, b"pong");
+///     let err = stream.read(&mut buf).await.unwrap_err();
+///     assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+/// });
+/// ```
+#[derive(Debug)]
+pub struct MockStream {
+    actions: VecDeque<Action>,
+    timer: Option<Timer>,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl MockStream {
+    /// Create a [`MockBuilder`]
+    pub fn builder() -> MockBuilder {
+        MockBuilder::new()
+    }
+
+    // Move on to the next action, waking up any reads or writes that were waiting on this one
+    fn next_action(&mut self) -> Option<Action> {
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+        self.actions.pop_front()
+    }
+
+    fn poll_wait(&mut self, duration: Duration, cx: &mut Context<'_>) -> Poll<()> {
+        let timer = self.timer.get_or_insert_with(|| Timer::delay(duration));
+        ready!(Pin::new(timer).poll(cx));
+        self.timer = None;
+        self.next_action();
+        Poll::Ready(())
+    }
+}
+
+impl AsyncRead for MockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            match this.actions.front_mut() {
+                None => return Poll::Ready(Ok(0)),
+                Some(Action::Wait(duration)) => {
+                    let duration = *duration;
+                    this.read_waker = Some(cx.waker().clone());
+                    ready!(this.poll_wait(duration, cx));
+                }
+                Some(Action::Read(data)) => {
+                    let n = buf.len().min(data.len());
+                    buf[..n].copy_from_slice(&data[..n]);
+                    data.drain(..n);
+                    if data.is_empty() {
+                        this.next_action();
+                    }
+                    return Poll::Ready(Ok(n));
+                }
+                Some(Action::ReadError(_)) => {
+                    let Some(Action::ReadError(err)) = this.next_action() else {
+                        unreachable!()
+                    };
+                    return Poll::Ready(Err(err));
+                }
+                // Wait for the write to happen
+                Some(Action::Write(_) | Action::WriteError(_)) => {
+                    this.read_waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            match this.actions.front_mut() {
+                None => panic!("unexpected write to finished mock stream: {buf:?}"),
+                Some(Action::Wait(duration)) => {
+                    let duration = *duration;
+                    this.write_waker = Some(cx.waker().clone());
+                    ready!(this.poll_wait(duration, cx));
+                }
+                Some(Action::Write(expected)) => {
+                    let n = buf.len().min(expected.len());
+                    assert_eq!(
+                        &buf[..n],
+                        &expected[..n],
+                        "unexpected data written to mock stream"
+                    );
+                    expected.drain(..n);
+                    if expected.is_empty() {
+                        this.next_action();
+                    }
+                    return Poll::Ready(Ok(n));
+                }
+                Some(Action::WriteError(_)) => {
+                    let Some(Action::WriteError(err)) = this.next_action() else {
+                        unreachable!()
+                    };
+                    return Poll::Ready(Err(err));
+                }
+                // Wait for the read to happen
+                Some(Action::Read(_) | Action::ReadError(_)) => {
+                    this.write_waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for MockStream {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            if let Some(action) = self.actions.front() {
+                panic!("mock stream dropped before the script finished, next action: {action:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::test::MockWaker;
+
+    use super::*;
+
+    #[test]
+    fn script() {
+        let read_waker = Arc::new(MockWaker::default());
+        let write_waker = Arc::new(MockWaker::default());
+        let read_waker_std = read_waker.clone().into();
+        let write_waker_std = write_waker.clone().into();
+        let rcx = &mut Context::from_waker(&read_waker_std);
+        let wcx = &mut Context::from_waker(&write_waker_std);
+        let mut stream = MockStream::builder()
+            .read(b"abc")
+            .write(b"xy")
+            .write_error(io::ErrorKind::BrokenPipe.into())
+            .build();
+        let mut buf = [0; 2];
+
+        // Writes wait for the script to reach them
+        assert!(Pin::new(&mut stream).poll_write(wcx, b"xy").is_pending());
+        assert!(matches!(
+            Pin::new(&mut stream).poll_read(rcx, &mut buf),
+            Poll::Ready(Ok(2))
+        ));
+        assert_eq!(&buf, b"ab");
+        assert!(!write_waker.get());
+        assert!(matches!(
+            Pin::new(&mut stream).poll_read(rcx, &mut buf),
+            Poll::Ready(Ok(1))
+        ));
+        assert_eq!(&buf[..1], b"c");
+        assert!(write_waker.get());
+
+        // Reads wait for the script to reach them
+        assert!(Pin::new(&mut stream).poll_read(rcx, &mut buf).is_pending());
+        assert!(matches!(
+            Pin::new(&mut stream).poll_write(wcx, b"x"),
+            Poll::Ready(Ok(1))
+        ));
+        assert!(!read_waker.get());
+        assert!(matches!(
+            Pin::new(&mut stream).poll_write(wcx, b"yz"),
+            Poll::Ready(Ok(1))
+        ));
+        assert!(read_waker.get());
+        assert!(matches!(
+            Pin::new(&mut stream).poll_write(wcx, b"z"),
+            Poll::Ready(Err(err)) if err.kind() == io::ErrorKind::BrokenPipe
+        ));
+
+        // Script is done, so reads return EOF
+        assert!(matches!(
+            Pin::new(&mut stream).poll_read(rcx, &mut buf),
+            Poll::Ready(Ok(0))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected data written to mock stream")]
+    fn wrong_write() {
+        let waker = Arc::new(MockWaker::default());
+        let std_waker = waker.into();
+        let cx = &mut Context::from_waker(&std_waker);
+        let mut stream = MockStream::builder().write(b"abc").build();
+        let _ = Pin::new(&mut stream).poll_write(cx, b"abd");
+    }
+
+    #[test]
+    #[should_panic(expected = "mock stream dropped before the script finished")]
+    fn unfinished() {
+        drop(MockStream::builder().read(b"abc").build());
+    }
+}
@@ -0,0 +1,355 @@
+//! Async standard I/O streams
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::fd::{AsFd, BorrowedFd},
+    pin::Pin,
+    sync::{Mutex, PoisonError},
+    task::{ready, Context, Poll},
+};
+
+use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+use rustix::fs::{FileType, OFlags};
+
+use crate::blocking::Unblock;
+
+use super::{set_nonblocking, Async, IoSafe};
+
+const STDIN_BUF_SIZE: usize = 8 * 1024;
+
+// Number of live non-blocking handles on a stdio stream, along with the file status flags the
+// stream had before it was made non-blocking
+type FlagState = Mutex<(usize, OFlags)>;
+
+static STDIN_FLAGS: FlagState = Mutex::new((0, OFlags::empty()));
+
+// Duplicate of a stdio FD that has been set to non-blocking mode
+//
+// Non-blocking mode is set on the file description, which is shared with the original FD and
+// possibly with other processes. The original flags are restored once the last handle on the
+// stream is dropped.
+struct StdioFd {
+    file: File,
+    flags: &'static FlagState,
+}
+
+impl StdioFd {
+    // Returns `None` if the FD isn't a pipe or socket, since non-blocking mode doesn't work on
+    // regular files, and affects other processes sharing the same terminal
+    fn new(fd: BorrowedFd, flags: &'static FlagState) -> io::Result<Option<Self>> {
+        let file_type = FileType::from_raw_mode(rustix::fs::fstat(fd)?.st_mode as _);
+        if !matches!(file_type, FileType::Fifo | FileType::Socket) {
+            return Ok(None);
+        }
+        let fd = rustix::io::fcntl_dupfd_cloexec(fd, 0)?;
+
+        let mut state = flags.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.0 == 0 {
+            state.1 = rustix::fs::fcntl_getfl(&fd)?;
+            set_nonblocking(fd.as_fd())?;
+        }
+        state.0 += 1;
+        Ok(Some(Self {
+            file: fd.into(),
+            flags,
+        }))
+    }
+}
+
+impl Drop for StdioFd {
+    fn drop(&mut self) {
+        let mut state = self.flags.lock().unwrap_or_else(PoisonError::into_inner);
+        state.0 -= 1;
+        if state.0 == 0 {
+            if let Err(err) = rustix::fs::fcntl_setfl(&self.file, state.1) {
+                log::error!("Failed to restore stdio flags: {err}");
+            }
+        }
+    }
+}
+
+impl AsFd for StdioFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl Read for StdioFd {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+// SAFETY: The I/O trait impls only forward to `File`
+unsafe impl IoSafe for StdioFd {}
+
+enum Inner<S> {
+    NonBlocking(Async<StdioFd>),
+    Blocking(Unblock<S>),
+}
+
+impl<S: Send + 'static> Inner<S> {
+    fn new(std: S, fd: BorrowedFd, flags: &'static FlagState) -> Self {
+        match StdioFd::new(fd, flags).and_then(|fd| fd.map(Async::without_nonblocking).transpose())
+        {
+            Ok(Some(io)) => Inner::NonBlocking(io),
+            Ok(None) => Inner::Blocking(Unblock::new(std)),
+            Err(err) => {
+                log::warn!("Falling back to blocking stdio: {err}");
+                Inner::Blocking(Unblock::new(std))
+            }
+        }
+    }
+}
+
+/// Constructs a new async handle to the standard input of the current process
+///
+/// If standard input is a pipe or socket, the handle reads from it in non-blocking mode via the
+/// reactor. Otherwise, such as when reading from a terminal or regular file, reads are run on the
+/// [blocking thread pool](crate::blocking), since setting a terminal to non-blocking mode affects
+/// every other process attached to it.
+///
+/// Reads are buffered, so data may be read past what the handle has returned. Avoid mixing this
+/// handle with [`std::io::stdin`], since any data buffered by one will be missed by the other.
+///
+/// # Non-blocking mode
+///
+/// Non-blocking mode is set on the file description behind standard input, which is shared by
+/// the whole process, and possibly by other processes using the same pipe. While any handle is
+/// alive, blocking reads from the same pipe, such as through [`std::io::stdin`], fail with
+/// [`WouldBlock`](io::ErrorKind::WouldBlock) instead of waiting for data. The original flags are
+/// restored once all handles are dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures_lite::{AsyncBufReadExt, StreamExt};
+/// use local_runtime::io::stdin;
+///
+/// # local_runtime::block_on(async {
+/// let mut lines = stdin().lines();
+/// while let Some(line) = lines.next().await {
+///     println!("{}", line?);
+/// }
+/// # Ok::<_, std::io::Error>(())
+/// # });
+/// ```
+pub fn stdin() -> Stdin {
+    Stdin::from_fd(io::stdin(), io::stdin().as_fd(), &STDIN_FLAGS)
+}
+
+/// Constructs a new async handle to the standard output of the current process
+///
+/// Writes are run on the [blocking thread pool](crate::blocking), and go through the buffer of
+/// [`std::io::stdout`]. Unlike [`stdin`], standard output is never set to non-blocking mode, since
+/// that would make [`println!`] panic whenever the pipe is full.
+pub fn stdout() -> Stdout {
+    Stdout {
+        inner: Unblock::new(io::stdout()),
+    }
+}
+
+/// Constructs a new async handle to the standard error of the current process
+///
+/// Writes are run on the [blocking thread pool](crate::blocking), for the same reasons as
+/// [`stdout`].
+pub fn stderr() -> Stderr {
+    Stderr {
+        inner: Unblock::new(io::stderr()),
+    }
+}
+
+/// Async handle to the standard input of the current process
+///
+/// Created by [`stdin`].
+pub struct Stdin {
+    inner: Inner<io::Stdin>,
+    // Read buffer for non-blocking mode, since `Unblock` has its own buffer
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Stdin {
+    fn from_fd(std: io::Stdin, fd: BorrowedFd, flags: &'static FlagState) -> Self {
+        Self {
+            inner: Inner::new(std, fd, flags),
+            buf: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl AsyncRead for Stdin {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match &mut this.inner {
+            Inner::NonBlocking(io) => {
+                // Skip our buffer if it's empty and the read is large enough
+                if this.pos == this.buf.len() && buf.len() >= STDIN_BUF_SIZE {
+                    return Pin::new(io).poll_read(cx, buf);
+                }
+                let data = ready!(Pin::new(&mut *this).poll_fill_buf(cx))?;
+                let n = data.len().min(buf.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                this.pos += n;
+                Poll::Ready(Ok(n))
+            }
+            Inner::Blocking(io) => io.poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncBufRead for Stdin {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        match &mut this.inner {
+            Inner::NonBlocking(io) => {
+                if this.pos == this.buf.len() {
+                    this.buf.resize(STDIN_BUF_SIZE, 0);
+                    let res = Pin::new(io).poll_read(cx, &mut this.buf);
+                    let n = match &res {
+                        Poll::Ready(Ok(n)) => *n,
+                        _ => 0,
+                    };
+                    this.buf.truncate(n);
+                    this.pos = 0;
+                    ready!(res)?;
+                }
+                Poll::Ready(Ok(&this.buf[this.pos..]))
+            }
+            Inner::Blocking(io) => io.poll_fill_buf(cx),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        match &mut this.inner {
+            Inner::NonBlocking(_) => this.pos = (this.pos + amt).min(this.buf.len()),
+            Inner::Blocking(io) => io.consume(amt),
+        }
+    }
+}
+
+/// Async handle to the standard output of the current process
+///
+/// Created by [`stdout`].
+pub struct Stdout {
+    inner: Unblock<io::Stdout>,
+}
+
+impl AsyncWrite for Stdout {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Async handle to the standard error of the current process
+///
+/// Created by [`stderr`].
+pub struct Stderr {
+    inner: Unblock<io::Stderr>,
+}
+
+impl AsyncWrite for Stderr {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use futures_lite::{AsyncBufReadExt, StreamExt};
+    use rustix::pipe::pipe;
+
+    use crate::{block_on, time::sleep};
+
+    use super::*;
+
+    fn is_nonblocking(fd: impl AsFd) -> bool {
+        rustix::fs::fcntl_getfl(fd)
+            .unwrap()
+            .contains(OFlags::NONBLOCK)
+    }
+
+    #[test]
+    fn pipe_restore_flags() {
+        static FLAGS: FlagState = Mutex::new((0, OFlags::empty()));
+        let (read, _write) = pipe().unwrap();
+        assert!(!is_nonblocking(&read));
+
+        let stdin1 = Stdin::from_fd(io::stdin(), read.as_fd(), &FLAGS);
+        let stdin2 = Stdin::from_fd(io::stdin(), read.as_fd(), &FLAGS);
+        assert!(matches!(stdin1.inner, Inner::NonBlocking(_)));
+        assert!(is_nonblocking(&read));
+        drop(stdin1);
+        // Flags are only restored once all handles are dropped
+        assert!(is_nonblocking(&read));
+        drop(stdin2);
+        assert!(!is_nonblocking(&read));
+    }
+
+    #[test]
+    fn devnull_blocking() {
+        static FLAGS: FlagState = Mutex::new((0, OFlags::empty()));
+        let null = File::open("/dev/null").unwrap();
+
+        let stdin = Stdin::from_fd(io::stdin(), null.as_fd(), &FLAGS);
+        assert!(matches!(stdin.inner, Inner::Blocking(_)));
+        assert!(!is_nonblocking(&null));
+    }
+
+    #[test]
+    fn pipe_lines() {
+        static FLAGS: FlagState = Mutex::new((0, OFlags::empty()));
+        let (read, write) = pipe().unwrap();
+        let mut stdin = Stdin::from_fd(io::stdin(), read.as_fd(), &FLAGS);
+
+        block_on(async {
+            rustix::io::write(&write, b"hello\n").unwrap();
+            let mut line = String::new();
+            stdin.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "hello\n");
+
+            // Timers should keep running while waiting for input
+            let writer = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(30));
+                rustix::io::write(&write, b"a\nb\n").unwrap();
+            });
+            sleep(Duration::from_millis(10)).await;
+            let lines: Vec<_> = (&mut stdin).lines().take(2).try_collect().await.unwrap();
+            assert_eq!(lines, ["a", "b"]);
+            writer.join().unwrap();
+        });
+    }
+}
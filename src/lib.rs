@@ -23,10 +23,24 @@
 //! [`poll`](https://pubs.opengroup.org/onlinepubs/9699919799/functions/poll.html). Currently,
 //! Windows is not supported.
 //!
+//! The reactor is created with default settings the first time it's used. To configure it, see
+//! [`runtime::Builder`].
+//!
 //! # Concurrency
 //!
-//! The [`Executor`] can spawn tasks that run concurrently on the same thread. Alternatively, this
-//! crate provides macros such as [`join`] and [`merge_futures`] for concurrent execution.
+//! The [`Executor`] can spawn tasks that run concurrently on the same thread, and
+//! [`Executor::scope`] can spawn tasks that are guaranteed to finish before the scope returns.
+//! Alternatively, this crate provides macros such as [`join`], [`try_join`], [`select`], and
+//! [`merge_futures`] for concurrent execution. [`FuturesUnordered`] and [`SelectAll`] run a
+//! dynamic number of futures and streams concurrently without spawning them.
+//!
+//! Tasks can be asked to stop cooperatively with a [`CancellationToken`] from the [`cancel`]
+//! module. [`Executor::run_with_shutdown`] uses one to give unfinished tasks a grace period to
+//! wind down, instead of dropping them as soon as the main future completes.
+//!
+//! Blocking operations can be moved off of the runtime thread with [`spawn_blocking`], which runs
+//! them on a separate [thread pool](crate::blocking). The [`fs`] module uses the same thread pool
+//! to provide async file access.
 //!
 //! # Compatibility
 //!
@@ -35,6 +49,28 @@
 //! this crate, such as [`Async`] and timers, rely on the reactor to wake up, **they can only be
 //! driven by [`block_on`], and are not compatible with other runtimes**.
 //!
+//! With the `hyper` feature enabled, the `hyper` module lets the
+//! [`hyper`](https://docs.rs/hyper) HTTP library run on this runtime.
+//!
+//! # Testing
+//!
+//! With the `macros` feature enabled, `#[local_runtime::test]` runs an async test on an
+//! [`Executor`], optionally with a [paused clock](time::pause), a shuffle seed, and a timeout.
+//! `#[local_runtime::main]` does the same for `async fn main`. The
+//! [`io`] module provides in-memory streams for testing I/O code without real sockets.
+//!
+//! **Since the attribute is exported as `local_runtime::test`, `use local_runtime::*` conflicts
+//! with the builtin `#[test]` attribute**, and every `#[test]` in scope fails to compile as
+//! ambiguous. Either import the items you need by name, or add `use std::prelude::v1::test;`
+//! next to the glob import to pick the builtin attribute.
+//!
+//! # Instrumentation
+//!
+//! Tasks can be named and inspected with the [`task`] module, while the [`metrics`] and [`hooks`]
+//! modules expose the internals of the executor and reactor. With the `tracing` feature enabled,
+//! the runtime also emits [`tracing`](https://docs.rs/tracing) spans for each task and reactor
+//! wait, along with events for task spawns, polls, wakeups, completions, and cancellations.
+//!
 //! # Examples
 //!
 //! Listen for connections on a local port, while concurrently making connections to localhost.
@@ -75,9 +111,19 @@
 //! # }
 //! ```
 
+pub mod blocking;
+pub mod cancel;
 mod concurrency;
+pub mod fs;
+pub mod hooks;
+#[cfg(feature = "hyper")]
+pub mod hyper;
 pub mod io;
+pub mod metrics;
 mod reactor;
+pub mod runtime;
+mod shuffle;
+pub mod task;
 #[cfg(test)]
 mod test;
 pub mod time;
@@ -91,22 +137,36 @@ use std::{
     pin::{pin, Pin},
     rc::Rc,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
     task::{Context, Poll, Wake, Waker},
     thread::{self, ThreadId},
+    time::{Duration, Instant},
 };
 
 use atomic_waker::AtomicWaker;
+use cancel::CancellationToken;
 use concurrent_queue::ConcurrentQueue;
 use futures_core::future::LocalBoxFuture;
 use slab::Slab;
 
+pub use blocking::spawn_blocking;
 #[doc(hidden)]
-pub use concurrency::{JoinFuture, MergeFutureStream, MergeStream};
+pub use concurrency::{
+    FlattenTuple, JoinBranch, JoinBranches, JoinEnd, JoinFuture, JoinTupleFuture,
+    MergeFutureStream, MergeStream, SelectBranch, SelectBranches, SelectEnd, SelectFuture,
+    SelectOutput,
+};
+pub use concurrency::{FuturesUnordered, SelectAll};
+use hooks::TaskHooks;
 pub use io::Async;
+#[cfg(feature = "macros")]
+pub use local_runtime_macros::{main, test};
+use metrics::ExecutorMetrics;
 use reactor::{Notifier, REACTOR};
+use shuffle::{SeedGuard, Shuffler};
+use task::{CurrentTask, TaskId, TaskInfo};
 
 // Option<Id> will be same size as `usize`
 #[repr(transparent)]
@@ -147,7 +207,7 @@ impl Wake for Notifier {
 ///     Timer::delay(Duration::from_millis(10)).await;
 /// });
 /// ```
-pub fn block_on<T, F>(mut fut: F) -> T
+pub fn block_on<T, F>(fut: F) -> T
 where
     F: Future<Output = T>,
 {
@@ -169,12 +229,49 @@ where
     }
 }
 
+/// Scheduling priority of a task
+///
+/// Each time the executor runs, it polls all awoken tasks, starting with the high-priority tasks
+/// and ending with the low-priority tasks. Since every task that was awoken before the executor
+/// started running gets polled, lower-priority tasks are never starved. If the executor has a
+/// [budget](runtime::Builder::budget) and runs out of it, the next run starts from the priority
+/// after the one where it stopped, so that each priority still gets its turn.
+///
+/// See [`Executor::spawn_with_priority`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Priority {
+    /// For latency-sensitive tasks, such as control signals and health checks
+    High,
+    /// The default priority
+    #[default]
+    Normal,
+    /// For background tasks, such as bulk data transfers
+    Low,
+}
+
+impl Priority {
+    const COUNT: usize = 3;
+
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct WakeQueue {
     base_waker: AtomicWaker,
     local_thread: ThreadId,
-    local: UnsafeCell<VecDeque<usize>>,
-    concurrent: ConcurrentQueue<usize>,
+    // One queue for each priority, from highest to lowest. Each wakeup is flagged with whether it
+    // came from another thread.
+    local: UnsafeCell<[VecDeque<(usize, bool)>; Priority::COUNT]>,
+    concurrent: ConcurrentQueue<(usize, Priority)>,
+    // Priority that the next drain starts from. When a limited drain runs out, the next drain
+    // starts after the priority where it stopped, so that lower priorities aren't starved.
+    start: AtomicUsize,
 }
 
 // SAFETY: The thread-unsafety comes from `local`, which will only be accessed if the current
@@ -187,45 +284,85 @@ impl WakeQueue {
         Self {
             base_waker: AtomicWaker::new(),
             local_thread: thread::current().id(),
-            local: UnsafeCell::new(VecDeque::with_capacity(capacity)),
+            local: UnsafeCell::new([
+                VecDeque::new(),
+                VecDeque::with_capacity(capacity),
+                VecDeque::new(),
+            ]),
             concurrent: ConcurrentQueue::unbounded(),
+            start: AtomicUsize::new(0),
         }
     }
 
+    #[cfg(test)]
     fn push(&self, val: usize) {
+        self.push_with_priority(val, Priority::Normal);
+    }
+
+    fn push_with_priority(&self, val: usize, priority: Priority) {
         if thread::current().id() == self.local_thread {
             // SAFETY: Like all other accesses to `local`, this access can only happen if current
             // thread is `local_thread`, and also has limited lifetime. As such, this access will
             // never cause a data race or collide with any other access of `local`.
-            unsafe { (*self.local.get()).push_back(val) };
+            unsafe { (*self.local.get())[priority.index()].push_back((val, false)) };
         } else {
             // If queue is closed, then just don't do anything
-            let _ = self.concurrent.push(val);
+            let _ = self.concurrent.push((val, priority));
         }
     }
 
-    fn drain_for_each<F: FnMut(usize)>(&self, mut f: F) {
+    fn drain_for_each<F: FnMut(usize)>(&self, f: F) {
+        self.drain_limited(usize::MAX, f);
+    }
+
+    // Process at most `limit` wakeups, leaving the rest in the queue. Returns the number of
+    // wakeups processed and how many of those came from other threads.
+    fn drain_limited<F: FnMut(usize)>(&self, limit: usize, mut f: F) -> (usize, usize) {
         if thread::current().id() == self.local_thread {
-            // SAFETY: Like all other accesses to `local`, this access can only happen if current
-            // thread is `local_thread`, and also has limited lifetime. As such, this access will
-            // never cause a data race or collide with any other access of `local`.
-            let local_len = unsafe { (*self.local.get()).len() };
             let con_len = self.concurrent.len();
+            // Move the concurrent wakeups into the local queues so that they're prioritized
+            for (val, priority) in self.concurrent.try_iter().take(con_len) {
+                // SAFETY: Like all other accesses to `local`, this access can only happen if
+                // current thread is `local_thread`, and also has limited lifetime. As such, this
+                // access will never cause a data race or collide with any other access of `local`.
+                unsafe { (*self.local.get())[priority.index()].push_back((val, true)) };
+            }
+            // SAFETY: We're on `local_thread`, and the reference to `local` only lives for this
+            // statement
+            let local_lens = unsafe { (*self.local.get()).each_ref().map(VecDeque::len) };
 
             log::trace!(
-                "{:?} {local_len} local wakeups, {con_len} concurrent wakeups",
+                "{:?} {local_lens:?} wakeups by priority, {con_len} concurrent wakeups",
                 std::thread::current().id()
             );
-
-            // Set upperbounds for the iteration on the two queues to ensure we never loop forever
-            // if the callback also adds values to the queue
-            for _ in 0..local_len {
-                let val = unsafe { (*self.local.get()).pop_front().unwrap() };
-                f(val);
-            }
-            for val in self.concurrent.try_iter().take(con_len) {
-                f(val);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(concurrent_wakeups = con_len, "drain wakeups");
+
+            // Set upperbounds for the iteration on the queues to ensure we never loop forever if
+            // the callback also adds values to the queue. Higher priorities are processed first,
+            // unless the previous drain ran out before getting to the lower priorities.
+            let mut processed = 0;
+            let mut concurrent = 0;
+            let start = self.start.swap(0, Ordering::Relaxed);
+            for idx in (start..Priority::COUNT).chain(0..start) {
+                if processed == limit {
+                    // Continue from the next priority, even if this one has wakeups left
+                    self.start.store(idx, Ordering::Relaxed);
+                    break;
+                }
+                let len = local_lens[idx].min(limit - processed);
+                for _ in 0..len {
+                    // SAFETY: We're on `local_thread`, and the reference to `local` is dropped
+                    // before the callback runs, since the callback may push to `local` as well
+                    let (val, remote) = unsafe { (*self.local.get())[idx].pop_front().unwrap() };
+                    concurrent += remote as usize;
+                    f(val);
+                }
+                processed += len;
             }
+            (processed, concurrent)
+        } else {
+            (0, 0)
         }
     }
 
@@ -235,8 +372,9 @@ impl WakeQueue {
             // thread is `local_thread`, and also has limited lifetime. As such, this access will
             // never cause a data race or collide with any other access of `local`.
             unsafe {
-                (*self.local.get()).clear();
-                (*self.local.get()).push_back(init_val);
+                let local = &mut *self.local.get();
+                local.iter_mut().for_each(VecDeque::clear);
+                local[Priority::Normal.index()].push_back((init_val, false));
             }
             // Pop all remaining elements
             while self.concurrent.pop().is_ok() {}
@@ -248,6 +386,9 @@ struct TaskWaker {
     queue: Arc<WakeQueue>,
     awoken: AtomicBool,
     task_id: usize,
+    priority: Priority,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl Wake for TaskWaker {
@@ -259,7 +400,12 @@ impl Wake for TaskWaker {
             .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
             .is_ok()
         {
-            self.queue.push(self.task_id);
+            // Only wakers of spawned tasks have spans
+            #[cfg(feature = "tracing")]
+            if !self.span.is_none() {
+                tracing::trace!(parent: &self.span, "wake");
+            }
+            self.queue.push_with_priority(self.task_id, self.priority);
             // Release memory ordering
             self.queue.base_waker.wake();
         }
@@ -267,16 +413,23 @@ impl Wake for TaskWaker {
 }
 
 impl TaskWaker {
-    fn new(queue: Arc<WakeQueue>, task_id: usize) -> Self {
+    fn new(queue: Arc<WakeQueue>, task_id: usize, priority: Priority) -> Self {
         Self {
             awoken: AtomicBool::new(false),
             queue,
             task_id,
+            priority,
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
         }
     }
 
     fn waker_pair(queue: Arc<WakeQueue>, task_id: usize) -> (Arc<Self>, Waker) {
-        let this = Arc::new(Self::new(queue, task_id));
+        Self::new(queue, task_id, Priority::Normal).into_pair()
+    }
+
+    fn into_pair(self) -> (Arc<Self>, Waker) {
+        let this = Arc::new(self);
         let waker = this.clone().into();
         (this, waker)
     }
@@ -289,12 +442,33 @@ impl TaskWaker {
 struct SpawnedTask<'a> {
     future: LocalBoxFuture<'a, ()>,
     handle_data: Rc<HandleData>,
+    name: Option<String>,
+    priority: Priority,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl SpawnedTask<'_> {
+    fn info(&self) -> TaskInfo {
+        TaskInfo {
+            id: self.handle_data.id,
+            name: self.name.clone(),
+            priority: self.priority,
+            poll_count: 0,
+            since_last_poll: None,
+            // Newly spawned tasks always get polled on the next run of the executor
+            queued: true,
+        }
+    }
 }
 
 struct Task<'a> {
     future: LocalBoxFuture<'a, ()>,
     handle_data: Rc<HandleData>,
     waker_pair: (Arc<TaskWaker>, Waker),
+    name: Option<String>,
+    poll_count: u64,
+    last_poll: Option<Instant>,
 }
 
 impl<'a> Task<'a> {
@@ -302,7 +476,19 @@ impl<'a> Task<'a> {
         let (waker_data, waker) = &self.waker_pair;
         // Reset this waker so that it can produce wakeups again
         waker_data.to_sleep();
-        self.future.as_mut().poll(&mut Context::from_waker(waker))
+        self.poll_count += 1;
+        let _current = CurrentTask::enter(self.handle_data.id);
+        #[cfg(feature = "tracing")]
+        let _span = waker_data.span.enter();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(poll_count = self.poll_count, "poll");
+
+        let poll = self.future.as_mut().poll(&mut Context::from_waker(waker));
+        #[cfg(feature = "tracing")]
+        if poll.is_ready() {
+            tracing::trace!("complete");
+        }
+        poll
     }
 
     fn from_spawned(spawned_task: SpawnedTask<'a>, waker_pair: (Arc<TaskWaker>, Waker)) -> Self {
@@ -313,6 +499,21 @@ impl<'a> Task<'a> {
             future: spawned_task.future,
             handle_data,
             waker_pair,
+            name: spawned_task.name,
+            poll_count: 0,
+            last_poll: None,
+        }
+    }
+
+    fn info(&self, now: Instant) -> TaskInfo {
+        let waker_data = &self.waker_pair.0;
+        TaskInfo {
+            id: self.handle_data.id,
+            name: self.name.clone(),
+            priority: waker_data.priority,
+            poll_count: self.poll_count,
+            since_last_poll: self.last_poll.map(|t| now.saturating_duration_since(t)),
+            queued: waker_data.awoken.load(Ordering::Relaxed),
         }
     }
 }
@@ -343,6 +544,16 @@ pub struct Executor<'a> {
     tasks: RefCell<Slab<Task<'a>>>,
     spawned: RefCell<Vec<SpawnedTask<'a>>>,
     wake_queue: Arc<WakeQueue>,
+    metrics: RefCell<ExecutorMetrics>,
+    hooks: TaskHooks<'a>,
+    // Max number of wakeups to process each time the executor is polled
+    budget: usize,
+    // Cancelled when the executor starts shutting down
+    shutdown: CancellationToken,
+    // Randomizes scheduling in test mode
+    shuffler: Option<Shuffler>,
+    // Whether to record when each task was last polled, which costs a clock read per poll
+    track_poll_times: bool,
 }
 
 impl Default for Executor<'_> {
@@ -368,16 +579,124 @@ impl<'a> Executor<'a> {
             tasks: RefCell::new(Slab::with_capacity(capacity)),
             spawned: RefCell::new(Vec::with_capacity(capacity)),
             wake_queue: Arc::new(WakeQueue::with_capacity(capacity)),
+            metrics: RefCell::default(),
+            hooks: TaskHooks::default(),
+            budget: usize::MAX,
+            shutdown: CancellationToken::new(),
+            shuffler: None,
+            track_poll_times: false,
         }
     }
 
+    /// Set a callback that runs right before each task is polled
+    ///
+    /// The callback receives the ID of the task. Along with [`Executor::after_task_poll`], this
+    /// can be used to profile tasks or to detect tasks that take too long to poll.
+    ///
+    /// The callback runs while the executor is polling its tasks, so calling [`Executor::dump`]
+    /// from it will panic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{cell::Cell, time::{Duration, Instant}};
+    /// use local_runtime::Executor;
+    ///
+    /// let start = Cell::new(Instant::now());
+    /// let slowest = Cell::new(Duration::ZERO);
+    /// let ex = Executor::new()
+    ///     .before_task_poll(|_| start.set(Instant::now()))
+    ///     .after_task_poll(|_| slowest.set(slowest.get().max(start.get().elapsed())));
+    /// ex.block_on(async {
+    ///     ex.spawn(async { std::thread::sleep(Duration::from_millis(10)) }).await;
+    /// });
+    /// assert!(slowest.get() >= Duration::from_millis(10));
+    /// ```
+    pub fn before_task_poll(mut self, f: impl Fn(TaskId) + 'a) -> Self {
+        self.hooks.before_poll = Some(Box::new(f));
+        self
+    }
+
+    /// Set a callback that runs right after each task is polled
+    ///
+    /// The callback receives the ID of the task. See [`Executor::before_task_poll`] for more
+    /// details.
+    pub fn after_task_poll(mut self, f: impl Fn(TaskId) + 'a) -> Self {
+        self.hooks.after_poll = Some(Box::new(f));
+        self
+    }
+
+    /// Record when each task was last polled, for [`Executor::dump`]
+    ///
+    /// This is off by default, since it reads the clock every time a task is polled. When it's
+    /// off, [`TaskInfo::since_last_poll`] is always `None`.
+    pub fn track_poll_times(mut self) -> Self {
+        self.track_poll_times = true;
+        self
+    }
+
+    /// Randomize the order in which tasks are polled, for testing
+    ///
+    /// Normally, awoken tasks are polled in the order that they're awoken, which hides bugs that
+    /// only occur with other interleavings of the tasks. In shuffle mode, the executor uses a
+    /// pseudo-random generator seeded with `seed` to shuffle the order in which awoken tasks and
+    /// newly spawned tasks are polled. Task priorities are ignored. The same seed produces the
+    /// same interleaving, as long as the tasks are awoken in the same order.
+    ///
+    /// If a task or the main future panics, the seed is printed to stderr so that the failing
+    /// interleaving can be replayed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use local_runtime::Executor;
+    ///
+    /// let run = |seed| {
+    ///     let order = RefCell::new(vec![]);
+    ///     let ex = Executor::new().shuffle(seed);
+    ///     ex.block_on(async {
+    ///         for i in 0..10 {
+    ///             let order = &order;
+    ///             ex.spawn(async move { order.borrow_mut().push(i) });
+    ///         }
+    ///     });
+    ///     order.take()
+    /// };
+    /// // The interleaving is deterministic for a given seed
+    /// assert_eq!(run(7), run(7));
+    /// ```
+    pub fn shuffle(mut self, seed: u64) -> Self {
+        self.shuffler = Some(Shuffler::new(seed));
+        self
+    }
+
+    /// Spuriously wake up tasks in shuffle mode, for testing
+    ///
+    /// After a task is polled and returns `Pending`, it's awoken again with the given
+    /// `probability`. Correct futures must handle spurious wakeups, so this helps catch futures
+    /// that assume every wakeup means progress.
+    ///
+    /// # Panic
+    ///
+    /// Panics if shuffle mode isn't enabled with [`Executor::shuffle`], or if `probability` isn't
+    /// between 0 and 1.
+    pub fn spurious_wakeups(mut self, probability: f64) -> Self {
+        self.shuffler
+            .as_mut()
+            .expect("spurious wakeups require shuffle mode")
+            .set_spurious_wakeups(probability);
+        self
+    }
+
     /// Spawn a task on the executor, returning a [`TaskHandle`] to it
     ///
     /// The provided future will run concurrently on the current thread while [`Executor::run`]
     /// runs, even if you don't await on the `TaskHandle`. If it's not awaited, there's no
     /// guarantee that the task will run to completion.
     ///
-    /// To spawn additional tasks from inside of a spawned task, see [`Executor::spawn_rc`].
+    /// To spawn additional tasks from inside of a spawned task, see [`Executor::spawn_rc`],
+    /// [`Executor::scope`], or [`spawn_local`].
     ///
     /// ```no_run
     /// use std::net::TcpListener;
@@ -398,12 +717,78 @@ impl<'a> Executor<'a> {
     /// # }
     /// ```
     pub fn spawn<T: 'a>(&self, fut: impl Future<Output = T> + 'a) -> TaskHandle<T> {
+        self.spawn_with_priority(Priority::Normal, fut)
+    }
+
+    /// Spawn a task with a scheduling [`Priority`], returning a [`TaskHandle`] to it
+    ///
+    /// Whenever the executor runs, awoken tasks with higher priority are polled before tasks with
+    /// lower priority. Lower-priority tasks still get polled during every run of the executor, or
+    /// within a few runs if the executor has a [budget](runtime::Builder::budget), so they're never
+    /// starved. Tasks spawned with [`Executor::spawn`] have [`Priority::Normal`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use futures_lite::future::yield_now;
+    /// use local_runtime::{Executor, Priority};
+    ///
+    /// let order = RefCell::new(vec![]);
+    /// let ex = Executor::new();
+    /// ex.block_on(async {
+    ///     let low = ex.spawn_with_priority(Priority::Low, async {
+    ///         yield_now().await;
+    ///         order.borrow_mut().push("low");
+    ///     });
+    ///     let high = ex.spawn_with_priority(Priority::High, async {
+    ///         yield_now().await;
+    ///         order.borrow_mut().push("high");
+    ///     });
+    ///     low.await;
+    ///     high.await;
+    /// });
+    /// assert_eq!(*order.borrow(), ["high", "low"]);
+    /// ```
+    pub fn spawn_with_priority<T: 'a>(
+        &self,
+        priority: Priority,
+        fut: impl Future<Output = T> + 'a,
+    ) -> TaskHandle<T> {
+        self.spawn_with_builder(task::Builder::new().priority(priority), fut)
+    }
+
+    pub(crate) fn spawn_with_builder<T: 'a>(
+        &self,
+        builder: task::Builder,
+        fut: impl Future<Output = T> + 'a,
+    ) -> TaskHandle<T> {
         let ret = Rc::new(RetData {
             value: Cell::new(None),
             waker: Cell::new(None),
         });
         let ret_clone = ret.clone();
-        let handle_data = Rc::<HandleData>::default();
+        let handle_data = Rc::new(HandleData {
+            id: TaskId::next(),
+            cancelled: Cell::new(false),
+            waker: Cell::new(None),
+        });
+
+        let mut metrics = self.metrics.borrow_mut();
+        metrics.live_tasks += 1;
+        metrics.spawned_tasks += 1;
+
+        // Task spans are root spans, since tasks can outlive the context they're spawned from
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            parent: None,
+            "task",
+            task.id = handle_data.id.as_u64(),
+            task.name = builder.name.as_deref(),
+            task.priority = ?builder.priority,
+        );
+        #[cfg(feature = "tracing")]
+        tracing::trace!(task.id = handle_data.id.as_u64(), "spawn");
 
         let mut spawned = self.spawned.borrow_mut();
         spawned.push(SpawnedTask {
@@ -416,6 +801,10 @@ impl<'a> Executor<'a> {
                 }
             }),
             handle_data: handle_data.clone(),
+            name: builder.name,
+            priority: builder.priority,
+            #[cfg(feature = "tracing")]
+            span,
         });
         TaskHandle { ret, handle_data }
     }
@@ -477,6 +866,85 @@ impl<'a> Executor<'a> {
         self.spawn(f(cl))
     }
 
+    /// List all live tasks on the executor, ordered by ID
+    ///
+    /// Each [`TaskInfo`] contains the task's ID, name, poll count, time since last poll, and
+    /// whether it's queued for polling. This is useful for debugging an executor that hangs. The
+    /// time since last poll is only available if [`Executor::track_poll_times`] is enabled.
+    /// Tasks that have been cancelled are not listed.
+    ///
+    /// # Panic
+    ///
+    /// Panics if called from inside a task on this executor, since the tasks are in use while
+    /// they're being polled. Calling it from the future passed to [`Executor::run`] is fine.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{future::pending, time::Duration};
+    /// use local_runtime::{task, time::sleep, Executor};
+    ///
+    /// let ex = Executor::new().track_poll_times();
+    /// ex.block_on(async {
+    ///     let handle = task::Builder::new()
+    ///         .name("stuck")
+    ///         .spawn(&ex, pending::<()>());
+    ///     sleep(Duration::from_millis(10)).await;
+    ///
+    ///     let dump = ex.dump();
+    ///     assert_eq!(dump.len(), 1);
+    ///     assert_eq!(dump[0].id(), handle.id());
+    ///     assert_eq!(dump[0].name(), Some("stuck"));
+    ///     assert_eq!(dump[0].poll_count(), 1);
+    ///     assert!(dump[0].since_last_poll().unwrap() >= Duration::from_millis(10));
+    ///     assert!(!dump[0].is_queued());
+    /// });
+    /// ```
+    pub fn dump(&self) -> Vec<TaskInfo> {
+        let now = Instant::now();
+        let tasks = self.tasks.borrow();
+        let spawned = self.spawned.borrow();
+        let mut infos: Vec<_> = tasks
+            .iter()
+            .map(|(_, task)| task)
+            .filter(|task| !task.handle_data.cancelled.get())
+            .map(|task| task.info(now))
+            .chain(
+                spawned
+                    .iter()
+                    .filter(|task| !task.handle_data.cancelled.get())
+                    .map(SpawnedTask::info),
+            )
+            .collect();
+        infos.sort_unstable_by_key(|info| info.id);
+        infos
+    }
+
+    /// Get a snapshot of the executor's metrics
+    ///
+    /// Unlike [`Executor::dump`], this is cheap and can be called from anywhere, including from
+    /// inside the executor's own tasks. See [`metrics::reactor_metrics`] for the reactor's metrics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use local_runtime::Executor;
+    ///
+    /// let ex = Executor::new();
+    /// ex.block_on(async {
+    ///     let handle = ex.spawn(async { 1 });
+    ///     assert_eq!(ex.metrics().live_tasks(), 1);
+    ///     handle.await;
+    ///     assert_eq!(ex.metrics().live_tasks(), 0);
+    /// });
+    /// let metrics = ex.metrics();
+    /// assert_eq!(metrics.spawned_tasks(), 1);
+    /// assert_eq!(metrics.task_polls(), 1);
+    /// ```
+    pub fn metrics(&self) -> ExecutorMetrics {
+        *self.metrics.borrow()
+    }
+
     fn register_base_waker(&self, base_waker: &Waker) {
         // Acquire ordering
         self.wake_queue.base_waker.register(base_waker);
@@ -484,34 +952,118 @@ impl<'a> Executor<'a> {
 
     // Poll tasks that have been awoken, returning whether the main future has been awoken
     fn poll_tasks(&self) -> bool {
+        let (main_task_awoken, processed) = self.poll_tasks_limited(self.budget);
+        // If we ran out of budget, there may be more wakeups in the queue, so come back later
+        if processed == self.budget {
+            self.wake_queue.base_waker.wake();
+        }
+        main_task_awoken
+    }
+
+    // Process at most `limit` wakeups, returning whether the main future has been awoken and the
+    // number of wakeups processed
+    fn poll_tasks_limited(&self, limit: usize) -> (bool, usize) {
         let mut main_task_awoken = false;
         let mut tasks = self.tasks.borrow_mut();
 
-        self.wake_queue.drain_for_each(|task_id| {
+        let process = |task_id| {
             if task_id == MAIN_TASK_ID {
                 main_task_awoken = true;
             }
             // For each awoken task, find it if it still exists
             else if let Some(task) = tasks.get_mut(task_id) {
                 // If a task is cancelled, don't poll it, just remove it
-                if task.handle_data.cancelled.get() || task.poll().is_ready() {
+                let cancelled = task.handle_data.cancelled.get();
+                #[cfg(feature = "tracing")]
+                if cancelled {
+                    tracing::trace!(parent: &task.waker_pair.0.span, "cancel");
+                }
+                if cancelled || self.poll_task(task).is_ready() {
                     tasks.remove(task_id);
+                    self.metrics.borrow_mut().live_tasks -= 1;
+                }
+                if !cancelled {
+                    self.metrics.borrow_mut().task_polls += 1;
                 }
             }
-        });
+        };
+        let (processed, concurrent) = match &self.shuffler {
+            None => self.wake_queue.drain_limited(limit, process),
+            Some(shuffler) => {
+                let mut awoken = vec![];
+                let res = self.wake_queue.drain_limited(limit, |id| awoken.push(id));
+                shuffler.shuffle(&mut awoken);
+                awoken.into_iter().for_each(process);
+                res
+            }
+        };
+        self.metrics
+            .borrow_mut()
+            .record_drain(processed, concurrent);
+        (main_task_awoken, processed)
+    }
 
-        main_task_awoken
+    fn poll_task(&self, task: &mut Task<'a>) -> Poll<()> {
+        let id = task.handle_data.id;
+        if let Some(f) = &self.hooks.before_poll {
+            f(id);
+        }
+        if self.track_poll_times {
+            task.last_poll = Some(Instant::now());
+        }
+        let poll = {
+            let _guard = SeedGuard(self.shuffler.as_ref().map(Shuffler::seed));
+            task.poll()
+        };
+        if let Some(f) = &self.hooks.after_poll {
+            f(id);
+        }
+        if poll.is_pending()
+            && self
+                .shuffler
+                .as_ref()
+                .is_some_and(Shuffler::spurious_wakeup)
+        {
+            task.waker_pair.1.wake_by_ref();
+        }
+        poll
+    }
+
+    // Take the next spawned task, which is a random one in shuffle mode
+    fn pop_spawned(&self) -> Option<SpawnedTask<'a>> {
+        let mut spawned = self.spawned.borrow_mut();
+        match &self.shuffler {
+            Some(shuffler) if !spawned.is_empty() => {
+                let idx = shuffler.index(spawned.len());
+                Some(spawned.swap_remove(idx))
+            }
+            _ => spawned.pop(),
+        }
     }
 
     // Poll newly spawned tasks and move them to the task list
     fn poll_spawned(&self) {
+        self.poll_spawned_limited(usize::MAX);
+    }
+
+    // Poll at most `limit` newly spawned tasks, returning the number of tasks polled. Cancelled
+    // tasks are removed without counting towards the limit.
+    fn poll_spawned_limited(&self, limit: usize) -> usize {
         let mut tasks = self.tasks.borrow_mut();
+        let mut polled = 0;
         // Keep checking newly spawned tasks until there's no more left.
         // Reborrow the spawned tasks on every iteration, because the tasks themselves also need to
         // borrow the spawned tasks.
-        while let Some(spawned_task) = self.spawned.borrow_mut().pop() {
+        while polled < limit {
+            // Don't pop in the loop condition, since that holds the borrow for the whole iteration
+            let Some(spawned_task) = self.pop_spawned() else {
+                break;
+            };
             // Ignore cancelled tasks
             if spawned_task.handle_data.cancelled.get() {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(parent: &spawned_task.span, "cancel");
+                self.metrics.borrow_mut().live_tasks -= 1;
                 continue;
             }
 
@@ -522,13 +1074,146 @@ impl<'a> Executor<'a> {
             // original task in the Slab. This should be rare, and at worse causes spurious wakeups.
             let task_id = next_vacancy.key();
             assert_ne!(task_id, MAIN_TASK_ID);
-            let waker_pair = TaskWaker::waker_pair(self.wake_queue.clone(), task_id);
-            let mut task = Task::from_spawned(spawned_task, waker_pair);
+            let waker_data =
+                TaskWaker::new(self.wake_queue.clone(), task_id, spawned_task.priority);
+            #[cfg(feature = "tracing")]
+            let waker_data = TaskWaker {
+                span: spawned_task.span.clone(),
+                ..waker_data
+            };
+            let mut task = Task::from_spawned(spawned_task, waker_data.into_pair());
             // Only insert the task if it returns pending
-            if task.poll().is_pending() {
+            let poll = self.poll_task(&mut task);
+            polled += 1;
+            let mut metrics = self.metrics.borrow_mut();
+            metrics.task_polls += 1;
+            if poll.is_pending() {
                 next_vacancy.insert(task);
+            } else {
+                metrics.live_tasks -= 1;
             }
         }
+        polled
+    }
+
+    /// Poll the tasks of the executor and run one iteration of the reactor, without blocking
+    /// longer than `timeout`
+    ///
+    /// First polls all tasks that are awoken or newly spawned, then runs one iteration of the
+    /// reactor with [`runtime::turn`], then polls the tasks that were awoken by the reactor.
+    /// Unlike [`Executor::run`], this doesn't take over the thread, so it can be used to embed the
+    /// executor in another event loop. See the [`runtime`] module for details.
+    ///
+    /// Returns whether there are unfinished tasks left on the executor.
+    ///
+    /// # Panic
+    ///
+    /// Calling this function within a task spawned on the same executor will panic.
+    pub fn turn(&self, timeout: Option<Duration>) -> std::io::Result<bool> {
+        let _current = CurrentExecutor::enter(self);
+        // Task wakeups wake up the reactor, which is what the host event loop should be waiting on
+        let waker = REACTOR.with(|r| r.notifier()).into();
+        self.register_base_waker(&waker);
+        self.poll_tasks();
+        self.poll_spawned();
+
+        let res = runtime::turn(timeout);
+        self.register_base_waker(&waker);
+        self.poll_tasks();
+        self.poll_spawned();
+        res?;
+        Ok(self.has_tasks())
+    }
+
+    /// Poll the tasks of the executor until none of them can make progress, without running the
+    /// reactor
+    ///
+    /// Tasks that are awoken or newly spawned are polled repeatedly until there are none left.
+    /// Tasks waiting on I/O or timers are left alone, since this function never blocks. This is
+    /// useful in tests for driving all tasks as far as possible before asserting on their state.
+    ///
+    /// Returns whether there are unfinished tasks left on the executor.
+    ///
+    /// If a task keeps waking itself up, such as by yielding in a loop, then this function never
+    /// returns.
+    ///
+    /// # Panic
+    ///
+    /// Calling this function within a task spawned on the same executor will panic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{cell::Cell, time::Duration};
+    /// use futures_lite::future::yield_now;
+    /// use local_runtime::{time::sleep, Executor};
+    ///
+    /// let count = Cell::new(0);
+    /// let ex = Executor::new();
+    /// ex.spawn(async {
+    ///     for _ in 0..3 {
+    ///         count.set(count.get() + 1);
+    ///         yield_now().await;
+    ///     }
+    /// });
+    /// ex.spawn(sleep(Duration::from_secs(10)));
+    ///
+    /// // The timer task is left unfinished
+    /// assert!(ex.run_until_stalled());
+    /// assert_eq!(count.get(), 3);
+    /// ```
+    pub fn run_until_stalled(&self) -> bool {
+        let _current = CurrentExecutor::enter(self);
+        let mut main_task_awoken = false;
+        loop {
+            let (main, processed) = self.poll_tasks_limited(usize::MAX);
+            main_task_awoken |= main;
+            let spawned = self.poll_spawned_limited(usize::MAX);
+            if processed == 0 && spawned == 0 {
+                break;
+            }
+        }
+        self.restore_main_wakeup(main_task_awoken);
+        self.has_tasks()
+    }
+
+    /// Poll at most one task of the executor, without running the reactor
+    ///
+    /// Polls the next awoken task in priority order. If no tasks are awoken, then polls one newly
+    /// spawned task instead. Returns `false` if there was no task to poll.
+    ///
+    /// # Panic
+    ///
+    /// Calling this function within a task spawned on the same executor will panic.
+    pub fn try_run_one(&self) -> bool {
+        let _current = CurrentExecutor::enter(self);
+        let mut main_task_awoken = false;
+        let ran = loop {
+            let (main, processed) = self.poll_tasks_limited(1);
+            main_task_awoken |= main;
+            if processed == 0 {
+                break self.poll_spawned_limited(1) > 0;
+            }
+            // Wakeups of the main future don't count, so try again
+            if !main {
+                break true;
+            }
+        };
+        self.restore_main_wakeup(main_task_awoken);
+        ran
+    }
+
+    // If the wakeup of the main future got drained outside of `run`, put it back so that `run`
+    // doesn't miss it
+    fn restore_main_wakeup(&self, main_task_awoken: bool) {
+        if main_task_awoken {
+            self.wake_queue
+                .push_with_priority(MAIN_TASK_ID, Priority::Normal);
+        }
+    }
+
+    fn has_tasks(&self) -> bool {
+        !self.tasks.borrow().is_empty() || !self.spawned.borrow().is_empty()
     }
 
     /// Blocking version of [`Executor::run`].
@@ -547,6 +1232,10 @@ impl<'a> Executor<'a> {
     /// When this function completes, it will drop all unfinished tasks that were spawned on the
     /// executor.
     ///
+    /// While the returned future is being polled, this executor is the thread's current executor,
+    /// so [`spawn_local`] will spawn tasks onto it. If `run` is nested, the innermost executor
+    /// being polled is the current one.
+    ///
     /// # Panic
     ///
     /// Polling the future returned by this function within a task spawned on the same executor will
@@ -581,17 +1270,134 @@ impl<'a> Executor<'a> {
     /// });
     /// ```
     pub async fn run<T>(&self, fut: impl Future<Output = T>) -> T {
+        let out = self.run_main(fut).await;
+        // Drop all unfinished tasks so that any Rc<Executor> inside the tasks are dropped. This
+        // prevents Rc-cycles and guarantees that the executor will be dropped later
+        drop(ClearTasks(self));
+        out
+    }
+
+    /// Blocking version of [`Executor::run_with_shutdown`].
+    ///
+    /// This is just a shorthand for calling `block_on(ex.run_with_shutdown(fut, grace_period))`.
+    ///
+    /// # Panic
+    ///
+    /// Calling this function within a task spawned on the same executor will panic.
+    pub fn block_on_with_shutdown<T>(
+        &self,
+        fut: impl Future<Output = T>,
+        grace_period: Duration,
+    ) -> (T, ShutdownReport) {
+        block_on(self.run_with_shutdown(fut, grace_period))
+    }
+
+    /// Like [`Executor::run`], but shuts down the executor gracefully once the future completes
+    ///
+    /// Instead of dropping the unfinished tasks right away, the executor cancels its
+    /// [shutdown token](Executor::shutdown_token) and keeps driving the tasks until they all
+    /// finish or until `grace_period` elapses, whichever comes first. Tasks that are still
+    /// unfinished after the grace period are dropped. The returned [`ShutdownReport`] counts the
+    /// tasks that finished during the grace period and the tasks that were dropped.
+    ///
+    /// # Panic
+    ///
+    /// Polling the future returned by this function within a task spawned on the same executor will
+    /// panic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{future::pending, time::Duration};
+    /// use local_runtime::{time::sleep, Executor};
+    ///
+    /// let ex = Executor::new();
+    /// let (_, report) = ex.block_on_with_shutdown(async {
+    ///     let token = ex.shutdown_token();
+    ///     // This task finishes its work once shutdown starts
+    ///     ex.spawn(async move {
+    ///         token.cancelled().await;
+    ///         sleep(Duration::from_millis(5)).await;
+    ///     });
+    ///     // This task never finishes
+    ///     ex.spawn(pending::<()>());
+    /// }, Duration::from_millis(50));
+    /// assert_eq!(report.drained(), 1);
+    /// assert_eq!(report.cancelled(), 1);
+    /// ```
+    pub async fn run_with_shutdown<T>(
+        &self,
+        fut: impl Future<Output = T>,
+        grace_period: Duration,
+    ) -> (T, ShutdownReport) {
+        let out = self.run_main(fut).await;
+
+        self.shutdown.cancel();
+        let unfinished = self.unfinished_tasks();
+        let spawned_tasks = self.metrics.borrow().spawned_tasks;
+        let mut grace_timer = pin!(time::sleep(grace_period));
+        poll_fn(|cx| {
+            let _current = CurrentExecutor::enter(self);
+            self.register_base_waker(cx.waker());
+            self.poll_tasks();
+            self.poll_spawned();
+            if self.has_tasks() {
+                grace_timer.as_mut().poll(cx).map(drop)
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await;
+
+        // Tasks spawned during the grace period also count towards the total
+        let total = unfinished + (self.metrics.borrow().spawned_tasks - spawned_tasks) as usize;
+        let cancelled = self.unfinished_tasks();
+        let report = ShutdownReport {
+            drained: total.saturating_sub(cancelled),
+            cancelled,
+        };
+        // Force-cancel the remaining tasks
+        drop(ClearTasks(self));
+        (out, report)
+    }
+
+    // Number of tasks that haven't finished or been cancelled
+    fn unfinished_tasks(&self) -> usize {
+        let tasks = self.tasks.borrow();
+        let spawned = self.spawned.borrow();
+        tasks
+            .iter()
+            .map(|(_, task)| &task.handle_data)
+            .chain(spawned.iter().map(|task| &task.handle_data))
+            .filter(|data| !data.cancelled.get())
+            .count()
+    }
+
+    /// Get the token that's cancelled when the executor starts shutting down
+    ///
+    /// The token is only cancelled by [`Executor::run_with_shutdown`], which gives the tasks a
+    /// grace period to finish after cancelling it. Tasks can wait on the token to wind down their
+    /// work before the executor drops them. Once cancelled, the token stays cancelled.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    // Drive the future to completion while driving the spawned tasks, without dropping the
+    // unfinished tasks afterwards
+    async fn run_main<T>(&self, fut: impl Future<Output = T>) -> T {
         let mut fut = pin!(fut);
         // Create waker for main future
         let (main_waker_data, main_waker) =
             TaskWaker::waker_pair(self.wake_queue.clone(), MAIN_TASK_ID);
         self.wake_queue.reset(MAIN_TASK_ID);
 
-        let out = poll_fn(move |cx| {
+        poll_fn(move |cx| {
+            let _current = CurrentExecutor::enter(self);
             self.register_base_waker(cx.waker());
             let main_task_awoken = self.poll_tasks();
             if main_task_awoken {
                 main_waker_data.to_sleep();
+                let _guard = SeedGuard(self.shuffler.as_ref().map(Shuffler::seed));
                 if let Poll::Ready(out) = fut.as_mut().poll(&mut Context::from_waker(&main_waker)) {
                     return Poll::Ready(out);
                 }
@@ -599,13 +1405,110 @@ impl<'a> Executor<'a> {
             self.poll_spawned();
             Poll::Pending
         })
-        .await;
+        .await
+    }
 
-        // Drop all unfinished tasks so that any Rc<Executor> inside the tasks are dropped. This
-        // prevents Rc-cycles and guarantees that the executor will be dropped later
-        self.tasks.borrow_mut().clear();
-        self.spawned.borrow_mut().clear();
-        out
+    /// Create a scope for spawning tasks that can borrow from the enclosing stack frame
+    ///
+    /// The closure receives a [`Scope`], which spawns tasks onto a new executor that's driven by
+    /// the returned future. Unlike [`Executor::run`], the returned future doesn't complete until
+    /// the future returned by the closure **and** every task spawned on the scope have completed.
+    /// If the returned future is dropped early, all unfinished tasks are dropped with it.
+    ///
+    /// Since [`Scope`] is cheap to clone, tasks can capture it to spawn more tasks, without
+    /// needing [`Executor::spawn_rc`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{cell::Cell, time::Duration};
+    /// use local_runtime::{block_on, time::sleep, Executor};
+    ///
+    /// let count = Cell::new(0);
+    /// let count_ref = &count;
+    /// block_on(Executor::scope(|s| async move {
+    ///     for i in 1..=3 {
+    ///         let s2 = s.clone();
+    ///         s.spawn(async move {
+    ///             sleep(Duration::from_millis(i)).await;
+    ///             // Spawn a nested task
+    ///             s2.spawn(async move { count_ref.set(count_ref.get() + i) });
+    ///         });
+    ///     }
+    /// }));
+    /// // All tasks have completed once the scope completes
+    /// assert_eq!(count.get(), 6);
+    /// ```
+    pub async fn scope<'s, T, F, Fut>(f: F) -> T
+    where
+        F: FnOnce(Scope<'s>) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        Self::run_scope(f, |_| false).await
+    }
+
+    /// Fallible version of [`Executor::scope`]
+    ///
+    /// If the future returned by the closure returns an error, all unfinished tasks spawned on the
+    /// scope are cancelled, and the error is returned immediately. Otherwise, the returned future
+    /// waits for all tasks to complete like [`Executor::scope`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::future::pending;
+    /// use local_runtime::{block_on, Executor};
+    ///
+    /// let res: Result<(), &str> = block_on(Executor::try_scope(|s| async move {
+    ///     // This task will never complete
+    ///     let _task = s.spawn(pending::<()>());
+    ///     Err("error")
+    /// }));
+    /// assert_eq!(res, Err("error"));
+    /// ```
+    pub async fn try_scope<'s, T, E, F, Fut>(f: F) -> Result<T, E>
+    where
+        F: FnOnce(Scope<'s>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        Self::run_scope(f, Result::is_err).await
+    }
+
+    async fn run_scope<'s, T, F, Fut>(f: F, cancel_on: impl Fn(&T) -> bool) -> T
+    where
+        F: FnOnce(Scope<'s>) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let ex = Rc::new(Executor::new());
+        // Drop the tasks when the scope is done or cancelled, since the tasks may hold references
+        // to the executor via `Scope`
+        let _guard = ClearTasks(&ex);
+        let mut fut = pin!(f(Scope { ex: ex.clone() }));
+        let mut out = None;
+        let (main_waker_data, main_waker) =
+            TaskWaker::waker_pair(ex.wake_queue.clone(), MAIN_TASK_ID);
+        ex.wake_queue.reset(MAIN_TASK_ID);
+
+        poll_fn(|cx| {
+            let _current = CurrentExecutor::enter(&ex);
+            ex.register_base_waker(cx.waker());
+            let main_task_awoken = ex.poll_tasks();
+            if main_task_awoken && out.is_none() {
+                main_waker_data.to_sleep();
+                if let Poll::Ready(val) = fut.as_mut().poll(&mut Context::from_waker(&main_waker)) {
+                    if cancel_on(&val) {
+                        return Poll::Ready(val);
+                    }
+                    out = Some(val);
+                }
+            }
+            ex.poll_spawned();
+            if out.is_some() && ex.tasks.borrow().is_empty() && ex.spawned.borrow().is_empty() {
+                return Poll::Ready(out.take().unwrap());
+            }
+            Poll::Pending
+        })
+        .await
     }
 }
 
@@ -614,8 +1517,8 @@ struct RetData<T> {
     waker: Cell<Option<Waker>>,
 }
 
-#[derive(Default)]
 struct HandleData {
+    id: TaskId,
     cancelled: Cell<bool>,
     waker: Cell<Option<Waker>>,
 }
@@ -626,9 +1529,11 @@ struct HandleData {
 /// result.
 ///
 /// A `TaskHandle` detaches its task when dropped. This means the it can no longer be awaited, but
-/// the executor will still poll its task.
+/// the executor will still poll its task. To cancel the task on drop instead, see
+/// [`TaskHandle::abort_on_drop`].
 ///
-/// This is created by [`Executor::spawn`] and [`Executor::spawn_rc`].
+/// This is created by [`Executor::spawn`], [`Executor::spawn_rc`], [`Scope::spawn`],
+/// [`task::Builder::spawn`], and [`spawn_local`].
 pub struct TaskHandle<T> {
     ret: Rc<RetData<T>>,
     handle_data: Rc<HandleData>,
@@ -663,6 +1568,40 @@ impl<T> TaskHandle<T> {
     pub fn is_cancelled(&self) -> bool {
         self.handle_data.cancelled.get()
     }
+
+    /// Get the ID of the task
+    ///
+    /// This is the same ID returned by [`task::id`] from inside the task.
+    pub fn id(&self) -> TaskId {
+        self.handle_data.id
+    }
+
+    /// Convert this handle into one that cancels the task when dropped
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{cell::Cell, time::Duration};
+    /// use local_runtime::{time::sleep, Executor};
+    ///
+    /// let flag = Cell::new(false);
+    /// let ex = Executor::new();
+    /// ex.block_on(async {
+    ///     let handle = ex
+    ///         .spawn(async {
+    ///             sleep(Duration::from_millis(10)).await;
+    ///             flag.set(true);
+    ///         })
+    ///         .abort_on_drop();
+    ///     drop(handle);
+    ///     sleep(Duration::from_millis(20)).await;
+    /// });
+    /// // The task was cancelled before it could finish
+    /// assert!(!flag.get());
+    /// ```
+    pub fn abort_on_drop(self) -> AbortOnDropHandle<T> {
+        AbortOnDropHandle { inner: Some(self) }
+    }
 }
 
 impl<T> Future for TaskHandle<T> {
@@ -683,6 +1622,215 @@ impl<T> Future for TaskHandle<T> {
     }
 }
 
+/// A handle to a spawned task that cancels the task when dropped
+///
+/// Other than cancelling on drop, this behaves like [`TaskHandle`]. This is useful for tying the
+/// lifetime of a task to the lifetime of some other object, such as a connection.
+///
+/// This is created by [`TaskHandle::abort_on_drop`].
+pub struct AbortOnDropHandle<T> {
+    // Only `None` after the handle has been detached
+    inner: Option<TaskHandle<T>>,
+}
+
+impl<T> AbortOnDropHandle<T> {
+    fn handle(&self) -> &TaskHandle<T> {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Cancel the task
+    ///
+    /// See [`TaskHandle::cancel`].
+    pub fn cancel(&self) {
+        self.handle().cancel();
+    }
+
+    /// Check if this task is finished
+    ///
+    /// See [`TaskHandle::is_finished`].
+    pub fn is_finished(&self) -> bool {
+        self.handle().is_finished()
+    }
+
+    /// Check if this task has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.handle().is_cancelled()
+    }
+
+    /// Get the ID of the task
+    pub fn id(&self) -> TaskId {
+        self.handle().id()
+    }
+
+    /// Convert back into a regular [`TaskHandle`], which detaches the task when dropped
+    pub fn detach(mut self) -> TaskHandle<T> {
+        self.inner.take().unwrap()
+    }
+}
+
+impl<T> Drop for AbortOnDropHandle<T> {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.inner {
+            handle.cancel();
+        }
+    }
+}
+
+impl<T> Future for AbortOnDropHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let handle = self.get_mut().inner.as_mut().unwrap();
+        Pin::new(handle).poll(cx)
+    }
+}
+
+/// A handle for spawning tasks within a scope
+///
+/// Tasks spawned via a `Scope` can borrow anything that outlives the scope, and are guaranteed to
+/// complete or be dropped before the scope completes.
+///
+/// This is created by [`Executor::scope`] and [`Executor::try_scope`].
+#[derive(Clone)]
+pub struct Scope<'s> {
+    ex: Rc<Executor<'s>>,
+}
+
+impl<'s> Scope<'s> {
+    /// Spawn a task on the scope, returning a [`TaskHandle`] to it
+    ///
+    /// The scope will wait for the task to complete even if the handle is dropped.
+    pub fn spawn<T: 's>(&self, fut: impl Future<Output = T> + 's) -> TaskHandle<T> {
+        self.ex.spawn(fut)
+    }
+
+    /// Spawn a task with a scheduling [`Priority`] on the scope
+    ///
+    /// See [`Executor::spawn_with_priority`].
+    pub fn spawn_with_priority<T: 's>(
+        &self,
+        priority: Priority,
+        fut: impl Future<Output = T> + 's,
+    ) -> TaskHandle<T> {
+        self.ex.spawn_with_priority(priority, fut)
+    }
+}
+
+// Drops all tasks of the executor
+struct ClearTasks<'r, 's>(&'r Executor<'s>);
+
+impl Drop for ClearTasks<'_, '_> {
+    fn drop(&mut self) {
+        // Move the tasks out before dropping them, in case their destructors access the executor
+        let tasks = std::mem::take(&mut *self.0.tasks.borrow_mut());
+        let spawned = std::mem::take(&mut *self.0.spawned.borrow_mut());
+        self.0.metrics.borrow_mut().live_tasks = 0;
+        drop(tasks);
+        drop(spawned);
+    }
+}
+
+thread_local! {
+    // The executor that's currently being polled on this thread. The lifetime is erased, so only
+    // `'static` futures can be spawned through this pointer.
+    static CURRENT: Cell<Option<*const Executor<'static>>> = const { Cell::new(None) };
+}
+
+// Sets the current executor, restoring the previous one on drop
+struct CurrentExecutor(Option<*const Executor<'static>>);
+
+impl CurrentExecutor {
+    fn enter(ex: &Executor) -> Self {
+        let ptr = (ex as *const Executor).cast::<Executor<'static>>();
+        Self(CURRENT.with(|c| c.replace(Some(ptr))))
+    }
+}
+
+impl Drop for CurrentExecutor {
+    fn drop(&mut self) {
+        CURRENT.with(|c| c.set(self.0));
+    }
+}
+
+/// Outcome of a graceful shutdown, returned by [`Executor::run_with_shutdown`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    drained: usize,
+    cancelled: usize,
+}
+
+impl ShutdownReport {
+    /// Number of tasks that finished during the grace period
+    pub fn drained(&self) -> usize {
+        self.drained
+    }
+
+    /// Number of tasks that were still unfinished after the grace period, and were dropped
+    pub fn cancelled(&self) -> usize {
+        self.cancelled
+    }
+}
+
+/// Error returned by [`try_spawn_local`] when there is no executor running on the current thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoExecutorError;
+
+impl std::fmt::Display for NoExecutorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no executor is running on the current thread")
+    }
+}
+
+impl std::error::Error for NoExecutorError {}
+
+/// Spawn a task on the executor that's currently running on this thread
+///
+/// The current executor is the one whose [`Executor::run`] (or [`Executor::block_on`]) is
+/// being polled, so this function can be called anywhere inside of it, including from spawned
+/// tasks, without passing the executor around. Since the task may outlive the caller, the future
+/// must be `'static`.
+///
+/// # Panic
+///
+/// Panics if no executor is running on the current thread. See [`try_spawn_local`] for a
+/// non-panicking version.
+///
+/// # Example
+///
+/// ```
+/// use local_runtime::{spawn_local, Executor};
+///
+/// let ex = Executor::new();
+/// let out = ex.block_on(async {
+///     let handle = spawn_local(async {
+///         // Spawn from inside a task
+///         spawn_local(async { 5 }).await
+///     });
+///     handle.await
+/// });
+/// assert_eq!(out, 5);
+/// ```
+pub fn spawn_local<T: 'static>(fut: impl Future<Output = T> + 'static) -> TaskHandle<T> {
+    match try_spawn_local(fut) {
+        Ok(handle) => handle,
+        Err(err) => panic!("spawn_local failed: {err}"),
+    }
+}
+
+/// Spawn a task on the executor that's currently running on this thread, returning an error if
+/// there is none
+///
+/// See [`spawn_local`] for more details.
+pub fn try_spawn_local<T: 'static>(
+    fut: impl Future<Output = T> + 'static,
+) -> Result<TaskHandle<T>, NoExecutorError> {
+    let ptr = CURRENT.with(Cell::get).ok_or(NoExecutorError)?;
+    // SAFETY: The pointer is only set while the executor is being polled, so it's still alive.
+    // The future is 'static, so it outlives the actual lifetime of the executor.
+    let ex = unsafe { &*ptr };
+    Ok(ex.spawn(fut))
+}
+
 #[cfg(test)]
 mod tests {
     use std::{future::pending, time::Duration};
@@ -690,6 +1838,8 @@ mod tests {
     use crate::{test::MockWaker, time::sleep};
 
     use super::*;
+    // Don't let the glob import of the `test` macro shadow the builtin attribute
+    use std::prelude::v1::test;
 
     #[test]
     fn spawn_and_poll() {
@@ -727,11 +1877,17 @@ mod tests {
 
         // Poll the spawned tasks, which should wake up right away
         ex.poll_spawned();
-        assert_eq!(unsafe { (*ex.wake_queue.local.get()).len() }, 1);
+        assert_eq!(
+            unsafe { (*ex.wake_queue.local.get())[Priority::Normal.index()].len() },
+            1
+        );
         assert!(base_waker.get());
         // Poll the awoken task, which should wake up again
         ex.poll_tasks();
-        assert_eq!(unsafe { (*ex.wake_queue.local.get()).len() }, 1);
+        assert_eq!(
+            unsafe { (*ex.wake_queue.local.get())[Priority::Normal.index()].len() },
+            1
+        );
 
         drop(ex);
         // Should have polled twice
@@ -764,6 +1920,268 @@ mod tests {
         assert_eq!(ex.tasks.borrow().len(), 0);
     }
 
+    #[test]
+    fn abort_on_drop() {
+        let ex = Executor::new();
+        let task = ex.spawn(pending::<()>()).abort_on_drop();
+        let detached = ex.spawn(pending::<()>()).abort_on_drop().detach();
+        ex.poll_tasks();
+        ex.poll_spawned();
+        assert_eq!(ex.tasks.borrow().len(), 2);
+
+        drop(task);
+        drop(detached);
+        ex.poll_tasks();
+        ex.poll_spawned();
+        // Only the task that wasn't detached should be cancelled
+        assert_eq!(ex.tasks.borrow().len(), 1);
+    }
+
+    #[test]
+    fn dump() {
+        let ex = Executor::new();
+        let waiting = task::Builder::new()
+            .name("waiting")
+            .spawn(&ex, pending::<()>());
+        let cancelled = ex.spawn(pending::<()>());
+        let awoken = ex.spawn_with_priority(
+            Priority::Low,
+            poll_fn(|cx| {
+                cx.waker().wake_by_ref();
+                Poll::<()>::Pending
+            }),
+        );
+        cancelled.cancel();
+
+        // Spawned tasks haven't been polled yet
+        let dump = ex.dump();
+        assert_eq!(dump.len(), 2);
+        assert_eq!(dump[0].id(), waiting.id());
+        assert_eq!(dump[0].name(), Some("waiting"));
+        assert_eq!(dump[0].poll_count(), 0);
+        assert_eq!(dump[0].since_last_poll(), None);
+        assert!(dump[0].is_queued());
+
+        ex.poll_spawned();
+        ex.poll_tasks();
+        let dump = ex.dump();
+        assert_eq!(dump.len(), 2);
+        assert_eq!(dump[0].poll_count(), 1);
+        assert!(!dump[0].is_queued());
+        assert_eq!(dump[1].id(), awoken.id());
+        assert_eq!(dump[1].name(), None);
+        assert_eq!(dump[1].priority(), Priority::Low);
+        assert_eq!(dump[1].poll_count(), 2);
+        // Poll times aren't tracked by default
+        assert_eq!(dump[1].since_last_poll(), None);
+        assert!(dump[1].is_queued());
+    }
+
+    #[test]
+    fn dump_poll_times() {
+        let ex = Executor::new().track_poll_times();
+        let handle = ex.spawn(pending::<()>());
+        assert_eq!(ex.dump()[0].since_last_poll(), None);
+
+        ex.poll_spawned();
+        let dump = ex.dump();
+        assert_eq!(dump[0].id(), handle.id());
+        assert!(dump[0].since_last_poll().is_some());
+    }
+
+    #[test]
+    fn metrics() {
+        let ex = Executor::new();
+        let _pending = ex.spawn(pending::<()>());
+        ex.spawn(async {});
+        let cancelled = ex.spawn(async {});
+        cancelled.cancel();
+        assert_eq!(ex.metrics().live_tasks(), 3);
+
+        ex.poll_spawned();
+        let metrics = ex.metrics();
+        assert_eq!(metrics.live_tasks(), 1);
+        assert_eq!(metrics.spawned_tasks(), 3);
+        assert_eq!(metrics.task_polls(), 2);
+
+        ex.wake_queue.push(MAIN_TASK_ID);
+        thread::scope(|s| {
+            s.spawn(|| ex.wake_queue.push(0));
+        });
+        ex.poll_tasks();
+        let metrics = ex.metrics();
+        assert_eq!(metrics.task_polls(), 3);
+        assert_eq!(metrics.drains(), 1);
+        assert_eq!(metrics.local_wakeups(), 1);
+        assert_eq!(metrics.concurrent_wakeups(), 1);
+        assert_eq!(metrics.wakeups(), 2);
+        assert_eq!(metrics.max_drain_wakeups(), 2);
+    }
+
+    #[test]
+    fn live_tasks_after_run() {
+        let ex = Executor::new();
+        ex.block_on(async {
+            ex.spawn(pending::<()>());
+            ex.spawn(pending::<()>());
+            assert_eq!(ex.metrics().live_tasks(), 2);
+        });
+        // The leftover tasks are dropped when the run ends
+        assert_eq!(ex.metrics().live_tasks(), 0);
+        ex.block_on(async {
+            ex.spawn(pending::<()>());
+        });
+        assert_eq!(ex.metrics().live_tasks(), 0);
+    }
+
+    #[test]
+    fn budget() {
+        let base_waker = Arc::new(MockWaker::default());
+        let mut ex = Executor::new();
+        ex.budget = 2;
+        ex.register_base_waker(&base_waker.clone().into());
+        for _ in 0..3 {
+            ex.spawn(poll_fn(|cx| {
+                cx.waker().wake_by_ref();
+                Poll::<()>::Pending
+            }));
+        }
+        ex.poll_spawned();
+        base_waker.set(false);
+        ex.register_base_waker(&base_waker.clone().into());
+
+        // Only 2 of the 3 awoken tasks get polled, and the executor wakes itself up again
+        ex.poll_tasks();
+        assert_eq!(ex.metrics().task_polls(), 5);
+        assert!(base_waker.get());
+        ex.register_base_waker(&base_waker.clone().into());
+        ex.poll_tasks();
+        assert_eq!(ex.metrics().task_polls(), 7);
+        assert_eq!(ex.metrics().max_drain_wakeups(), 2);
+    }
+
+    #[test]
+    fn budget_priorities() {
+        let mut ex = Executor::new();
+        ex.budget = 1;
+        let high = Rc::new(Cell::new(0));
+        let low = Rc::new(Cell::new(0));
+        let yielder = |polls: Rc<Cell<usize>>| {
+            poll_fn(move |cx| {
+                polls.set(polls.get() + 1);
+                cx.waker().wake_by_ref();
+                Poll::<()>::Pending
+            })
+        };
+        ex.spawn_with_priority(Priority::High, yielder(high.clone()));
+        ex.spawn_with_priority(Priority::High, yielder(high.clone()));
+        ex.spawn_with_priority(Priority::Low, yielder(low.clone()));
+        ex.poll_spawned();
+        ex.wake_queue.push(MAIN_TASK_ID);
+
+        // High-priority tasks that keep waking up don't starve the main future or the Low task
+        let mut main_task_awoken = false;
+        for _ in 0..30 {
+            main_task_awoken |= ex.poll_tasks();
+        }
+        assert!(main_task_awoken);
+        assert!(low.get() > 10);
+        assert!(high.get() > 10);
+    }
+
+    #[test]
+    fn budget_wakeup_metrics() {
+        let mut ex = Executor::new();
+        ex.budget = 1;
+        ex.wake_queue.push(MAIN_TASK_ID);
+        thread::scope(|s| {
+            s.spawn(|| ex.wake_queue.push(0));
+        });
+
+        // Wakeups from other threads are only counted once they're processed
+        ex.poll_tasks();
+        let metrics = ex.metrics();
+        assert_eq!(metrics.local_wakeups(), 1);
+        assert_eq!(metrics.concurrent_wakeups(), 0);
+        ex.poll_tasks();
+        let metrics = ex.metrics();
+        assert_eq!(metrics.local_wakeups(), 1);
+        assert_eq!(metrics.concurrent_wakeups(), 1);
+        assert_eq!(metrics.wakeups(), 2);
+    }
+
+    #[test]
+    fn try_run_one() {
+        let ex = Executor::new();
+        let polls = Rc::new(Cell::new(0));
+        let p = polls.clone();
+        ex.spawn(poll_fn(move |cx| {
+            p.set(p.get() + 1);
+            cx.waker().wake_by_ref();
+            Poll::<()>::Pending
+        }));
+        // Wakeups of the main future are skipped, but not lost
+        ex.wake_queue.push(MAIN_TASK_ID);
+
+        assert!(ex.try_run_one());
+        assert_eq!(polls.get(), 1);
+        assert!(ex.try_run_one());
+        assert_eq!(polls.get(), 2);
+        let mut elems = vec![];
+        ex.wake_queue.drain_for_each(|e| elems.push(e));
+        assert_eq!(elems, [MAIN_TASK_ID, 0]);
+        assert!(!ex.try_run_one());
+
+        // Cancelled tasks are skipped without counting as a run
+        let p = polls.clone();
+        ex.spawn(async move { p.set(p.get() + 1) });
+        ex.spawn(async {}).cancel();
+        assert!(ex.try_run_one());
+        assert_eq!(polls.get(), 3);
+        ex.spawn(async {}).cancel();
+        assert!(!ex.try_run_one());
+        assert_eq!(ex.metrics().live_tasks(), 1);
+    }
+
+    #[test]
+    fn shuffle() {
+        let run = |ex: Executor<'static>| {
+            let order = Rc::new(RefCell::new(vec![]));
+            ex.block_on(async {
+                for i in 0..10 {
+                    let order = order.clone();
+                    ex.spawn(async move {
+                        order.borrow_mut().push(i);
+                        futures_lite::future::yield_now().await;
+                        order.borrow_mut().push(i + 10);
+                    });
+                }
+                sleep(Duration::from_millis(1)).await;
+            });
+            (order.take(), ex.metrics().task_polls())
+        };
+
+        // Newly spawned tasks are normally polled in reverse order
+        let (default_order, polls) = run(Executor::new());
+        assert_eq!(
+            default_order,
+            (0..10).rev().chain((10..20).rev()).collect::<Vec<_>>()
+        );
+        assert_eq!(polls, 20);
+        let (order, polls) = run(Executor::new().shuffle(3));
+        assert_ne!(order[..10], default_order[..10]);
+        assert_ne!(order[10..], default_order[10..]);
+        assert_eq!(polls, 20);
+        assert_eq!(run(Executor::new().shuffle(3)).0, order);
+        assert_ne!(run(Executor::new().shuffle(4)).0, order);
+
+        // Every pending poll is followed by a spurious wakeup, so the task keeps getting polled
+        // while the timer is pending
+        let ex = Executor::new().shuffle(3).spurious_wakeups(1.0);
+        ex.block_on(ex.spawn(sleep(Duration::from_millis(5))));
+        assert!(ex.metrics().task_polls() > 2);
+    }
+
     #[test]
     fn wake_queue() {
         let queue = WakeQueue::with_capacity(4);
@@ -778,7 +2196,10 @@ mod tests {
         });
 
         assert_eq!(queue.concurrent.len(), 10);
-        assert_eq!(unsafe { (*queue.local.get()).len() }, 2);
+        assert_eq!(
+            unsafe { (*queue.local.get())[Priority::Normal.index()].len() },
+            2
+        );
 
         let mut elems = vec![];
         queue.drain_for_each(|e| elems.push(e));
@@ -789,10 +2210,39 @@ mod tests {
         queue.push(13);
         queue.reset(6);
         assert_eq!(queue.concurrent.len(), 0);
-        assert_eq!(unsafe { (*queue.local.get()).len() }, 1);
+        assert_eq!(
+            unsafe { (*queue.local.get())[Priority::Normal.index()].len() },
+            1
+        );
         queue.drain_for_each(|e| assert_eq!(e, 6));
     }
 
+    #[test]
+    fn wake_queue_priority() {
+        let queue = WakeQueue::with_capacity(4);
+        queue.push_with_priority(1, Priority::Low);
+        queue.push_with_priority(2, Priority::Normal);
+        queue.push_with_priority(3, Priority::High);
+        thread::scope(|s| {
+            s.spawn(|| queue.push_with_priority(4, Priority::Low));
+            s.spawn(|| queue.push_with_priority(5, Priority::High));
+        });
+
+        let mut elems = vec![];
+        queue.drain_for_each(|e| {
+            elems.push(e);
+            // Wakeups during the drain are processed on the next drain, even high priority ones
+            if e < 10 {
+                queue.push_with_priority(e + 10, Priority::High);
+            }
+        });
+        assert_eq!(elems, &[3, 5, 2, 1, 4]);
+
+        elems.clear();
+        queue.drain_for_each(|e| elems.push(e));
+        assert_eq!(elems, &[13, 15, 12, 11, 14]);
+    }
+
     #[test]
     fn switch_waker() {
         let ex = Executor::new();
@@ -0,0 +1,146 @@
+//! Runtime metrics
+//!
+//! The executor and the reactor keep a handful of counters that are updated as they run. These
+//! are just integer increments, so they're always enabled. Snapshots of the counters can be taken
+//! with [`Executor::metrics`] and [`reactor_metrics`].
+//!
+//! [`Executor::metrics`]: crate::Executor::metrics
+
+use std::time::Duration;
+
+use crate::reactor::REACTOR;
+
+/// Snapshot of an executor's metrics, returned by [`Executor::metrics`]
+///
+/// All counts are cumulative since the creation of the executor, except for
+/// [`live_tasks`](Self::live_tasks).
+///
+/// [`Executor::metrics`]: crate::Executor::metrics
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutorMetrics {
+    pub(crate) live_tasks: usize,
+    pub(crate) spawned_tasks: u64,
+    pub(crate) task_polls: u64,
+    pub(crate) drains: u64,
+    pub(crate) local_wakeups: u64,
+    pub(crate) concurrent_wakeups: u64,
+    pub(crate) max_drain_wakeups: u64,
+}
+
+impl ExecutorMetrics {
+    pub(crate) fn record_drain(&mut self, processed: usize, concurrent: usize) {
+        self.drains += 1;
+        self.local_wakeups += (processed - concurrent) as u64;
+        self.concurrent_wakeups += concurrent as u64;
+        self.max_drain_wakeups = self.max_drain_wakeups.max(processed as u64);
+    }
+
+    /// Number of tasks that are currently spawned on the executor and haven't finished
+    ///
+    /// Cancelled tasks are counted until the executor gets around to removing them.
+    pub fn live_tasks(&self) -> usize {
+        self.live_tasks
+    }
+
+    /// Total number of tasks spawned on the executor
+    pub fn spawned_tasks(&self) -> u64 {
+        self.spawned_tasks
+    }
+
+    /// Total number of times a task has been polled
+    pub fn task_polls(&self) -> u64 {
+        self.task_polls
+    }
+
+    /// Number of times the executor has processed its queue of wakeups
+    pub fn drains(&self) -> u64 {
+        self.drains
+    }
+
+    /// Total number of wakeups processed by the executor, including wakeups of the main future
+    pub fn wakeups(&self) -> u64 {
+        self.local_wakeups + self.concurrent_wakeups
+    }
+
+    /// Number of wakeups that came from the executor's thread
+    pub fn local_wakeups(&self) -> u64 {
+        self.local_wakeups
+    }
+
+    /// Number of wakeups that came from other threads
+    pub fn concurrent_wakeups(&self) -> u64 {
+        self.concurrent_wakeups
+    }
+
+    /// Largest number of wakeups processed in a single drain of the wakeup queue
+    pub fn max_drain_wakeups(&self) -> u64 {
+        self.max_drain_wakeups
+    }
+}
+
+/// Snapshot of the reactor's metrics, returned by [`reactor_metrics`]
+///
+/// All counts are cumulative since the reactor was created, except for
+/// [`event_sources`](Self::event_sources) and [`timers`](Self::timers).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReactorMetrics {
+    pub(crate) event_sources: usize,
+    pub(crate) timers: usize,
+    pub(crate) waits: u64,
+    pub(crate) polls: u64,
+    pub(crate) events: u64,
+    pub(crate) wait_time: Duration,
+}
+
+impl ReactorMetrics {
+    /// Number of I/O event sources currently registered on the reactor
+    pub fn event_sources(&self) -> usize {
+        self.event_sources
+    }
+
+    /// Number of timers currently registered on the reactor
+    pub fn timers(&self) -> usize {
+        self.timers
+    }
+
+    /// Number of times the reactor has waited for events
+    pub fn waits(&self) -> u64 {
+        self.waits
+    }
+
+    /// Number of times the reactor has polled the OS for events
+    ///
+    /// This can be lower than [`waits`](Self::waits), since the reactor skips polling the OS if
+    /// it knows the wait will end immediately.
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /// Total number of I/O events received from the OS
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+
+    /// Total time spent waiting for events
+    pub fn wait_time(&self) -> Duration {
+        self.wait_time
+    }
+}
+
+/// Get a snapshot of the metrics of the current thread's reactor
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use local_runtime::{block_on, metrics::reactor_metrics, time::sleep};
+///
+/// let before = reactor_metrics();
+/// block_on(sleep(Duration::from_millis(10)));
+/// let after = reactor_metrics();
+/// assert!(after.polls() > before.polls());
+/// assert!(after.wait_time() - before.wait_time() >= Duration::from_millis(10));
+/// ```
+pub fn reactor_metrics() -> ReactorMetrics {
+    REACTOR.with(|r| r.metrics())
+}
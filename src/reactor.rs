@@ -1,7 +1,7 @@
 mod unix;
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::BTreeMap,
     io,
     sync::{
@@ -12,7 +12,13 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{time::TimerQueue, Id};
+use crate::{
+    hooks::ParkHooks,
+    metrics::ReactorMetrics,
+    runtime::{NotifierBackend, PollerBackend, TimerBackend},
+    time::TimerQueue,
+    Id,
+};
 
 /// Type of event that we're interested in receiving
 #[derive(Debug, Clone, Copy)]
@@ -177,12 +183,15 @@ struct State<P> {
     poller: P,
     event_sources: BTreeMap<Source, EventData>,
     timer_queue: TimerQueue,
+    metrics: ReactorMetrics,
 }
 
 #[allow(private_bounds)]
 pub(crate) struct Reactor<P: EventPoller> {
     state: RefCell<State<P>>,
     notifier: Arc<WithFlag<P::Notifier>>,
+    // Kept outside of the state, since the hooks can access the reactor
+    park_hooks: RefCell<ParkHooks>,
 }
 
 /// General trait for the reactor used to wakeup futures
@@ -191,14 +200,20 @@ impl<P: EventPoller> Reactor<P> {
     /// Construct new reactor
     pub(crate) fn new() -> io::Result<Self> {
         let (poller, notifier) = P::new()?;
-        Ok(Reactor {
+        Ok(Self::from_parts(poller, notifier, ParkHooks::default()))
+    }
+
+    fn from_parts(poller: P, notifier: Arc<WithFlag<P::Notifier>>, park_hooks: ParkHooks) -> Self {
+        Reactor {
             state: RefCell::new(State {
                 poller,
                 event_sources: BTreeMap::new(),
                 timer_queue: TimerQueue::new(),
+                metrics: ReactorMetrics::default(),
             }),
             notifier,
-        })
+            park_hooks: RefCell::new(park_hooks),
+        }
     }
 
     #[cfg(unix)]
@@ -268,25 +283,82 @@ impl<P: EventPoller> Reactor<P> {
         !dir.enabled
     }
 
-    /// Wait for an event on the reactor with an optional timeout, then clears all event sources.
+    /// Wait for an event on the reactor until the next timer expires, then clears all event
+    /// sources.
     pub(crate) fn wait(&self) -> io::Result<()> {
-        let state = &mut *self.state.borrow_mut();
+        self.wait_timeout(None)
+    }
+
+    /// Wait for an event on the reactor until the next timer expires or `max_timeout` elapses,
+    /// then clears all event sources.
+    pub(crate) fn wait_timeout(&self, max_timeout: Option<Duration>) -> io::Result<()> {
+        // Only run the park hooks if we're going to block
+        let hooks =
+            (!self.is_wakeup_instant(max_timeout)).then(|| self.park_hooks.borrow().clone());
+        if let Some(hooks) = &hooks {
+            hooks.run_before_park();
+        }
+        let res = self.wait_inner(max_timeout);
+        if let Some(hooks) = &hooks {
+            hooks.run_after_unpark();
+        }
+        res
+    }
+
+    fn timeout(state: &mut State<P>, max_timeout: Option<Duration>) -> Option<Duration> {
         let timeout = state.timer_queue.next_timeout();
+        match (timeout, max_timeout) {
+            (Some(t), Some(max)) => Some(t.min(max)),
+            (t, max) => t.or(max),
+        }
+    }
+
+    fn is_wakeup_instant(&self, max_timeout: Option<Duration>) -> bool {
+        Self::timeout(&mut self.state.borrow_mut(), max_timeout) == Some(Duration::ZERO)
+            || self.notifier.is_notified()
+    }
 
-        if timeout == Some(Duration::ZERO) || self.notifier.is_notified() {
+    fn wait_inner(&self, max_timeout: Option<Duration>) -> io::Result<()> {
+        let state = &mut *self.state.borrow_mut();
+        let timeout = Self::timeout(state, max_timeout);
+        // With a paused clock, don't sleep until the next timer. Instead, check for I/O events
+        // without blocking, and jump the clock forward to the timer if nothing else happened.
+        // The clock never moves further than the caller's max timeout.
+        let paused_timeout = (crate::time::is_paused() && !state.timer_queue.is_empty())
+            .then_some(timeout)
+            .flatten()
+            .filter(|t| !t.is_zero());
+        let mut idle = !self.notifier.is_notified();
+        let start = Instant::now();
+        state.metrics.waits += 1;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("reactor.wait", ?timeout).entered();
+
+        // Callers that pass a max timeout want the I/O events to be collected even if the wait
+        // won't block, so only skip polling when waiting indefinitely
+        if max_timeout.is_none() && (timeout == Some(Duration::ZERO) || self.notifier.is_notified())
+        {
             log::trace!(
                 "{:?} Skip polling events since wakeup will be instant",
                 std::thread::current().id(),
             );
         } else {
             let event_sources = state.event_sources.iter().map(|(s, d)| (*s, d.filter()));
-            let revents = state.poller.poll(timeout, event_sources)?;
+            state.metrics.polls += 1;
+            let poll_timeout = paused_timeout.map_or(timeout, |_| Some(Duration::ZERO));
+            let revents = state.poller.poll(poll_timeout, event_sources)?;
             // Now that we have awaken from the poll call, there's no need to send any
             // notifications to "wake up" from the poll, so we set the notified flag to prevent
             // our wakers from sending any notifications.
             self.notifier.set_to_notified();
+            #[cfg(feature = "tracing")]
+            tracing::trace!(elapsed = ?start.elapsed(), "reactor unparked");
 
             for (source, filter) in revents.into_iter().flatten() {
+                idle = false;
+                state.metrics.events += 1;
+                #[cfg(feature = "tracing")]
+                tracing::trace!(source, read = filter.read, write = filter.write, "io event");
                 let data = state.event_sources.get_mut(&source).unwrap();
                 if filter.read {
                     data.read.wake();
@@ -297,10 +369,14 @@ impl<P: EventPoller> Reactor<P> {
             }
         }
 
+        if let (Some(t), true) = (paused_timeout, idle) {
+            crate::time::advance(t);
+        }
         // Clear expired timers from the timer queue
         state.timer_queue.clear_expired();
         // Clear notifier
         self.clear_notifications();
+        state.metrics.wait_time += start.elapsed();
         Ok(())
     }
 
@@ -328,6 +404,24 @@ impl<P: EventPoller> Reactor<P> {
         }
     }
 
+    /// Expiry of the earliest timer
+    pub(crate) fn next_timer_expiry(&self) -> Option<Instant> {
+        self.state.borrow().timer_queue.next_expiry()
+    }
+
+    pub(crate) fn set_park_hooks(&self, hooks: ParkHooks) -> ParkHooks {
+        self.park_hooks.replace(hooks)
+    }
+
+    pub(crate) fn metrics(&self) -> ReactorMetrics {
+        let state = self.state.borrow();
+        ReactorMetrics {
+            event_sources: state.event_sources.len(),
+            timers: state.timer_queue.len(),
+            ..state.metrics
+        }
+    }
+
     #[cfg(test)]
     pub(crate) fn is_empty(&self) -> bool {
         let state = self.state.borrow();
@@ -340,8 +434,46 @@ type Poller = unix::Poller;
 #[cfg(unix)]
 pub(crate) type Notifier = WithFlag<unix::PollerNotifier>;
 
+/// Configuration of the reactor, set by [`crate::runtime::Builder`]
+pub(crate) struct ReactorConfig {
+    pub(crate) poller: PollerBackend,
+    pub(crate) notifier: NotifierBackend,
+    pub(crate) timer: TimerBackend,
+    pub(crate) event_capacity: usize,
+    pub(crate) park_hooks: ParkHooks,
+}
+
 thread_local! {
-    pub(crate) static REACTOR: Reactor<Poller> = Reactor::new().expect("Failed to initialize reactor");
+    // Reactor built ahead of time by `install`, which `REACTOR` takes when it's initialized
+    static PREBUILT: Cell<Option<Reactor<Poller>>> = const { Cell::new(None) };
+    static INITIALIZED: Cell<bool> = const { Cell::new(false) };
+
+    pub(crate) static REACTOR: Reactor<Poller> = {
+        INITIALIZED.set(true);
+        match PREBUILT.take() {
+            Some(reactor) => reactor,
+            None => Reactor::new().expect("Failed to initialize reactor"),
+        }
+    };
+}
+
+/// Initialize the current thread's reactor with a custom config
+pub(crate) fn install(config: ReactorConfig) -> io::Result<()> {
+    if INITIALIZED.get() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "reactor already initialized on the current thread",
+        ));
+    }
+    let (poller, notifier) = Poller::with_config(&config)?;
+    PREBUILT.set(Some(Reactor::from_parts(
+        poller,
+        notifier,
+        config.park_hooks,
+    )));
+    // Force the reactor to initialize with the prebuilt reactor
+    REACTOR.with(|_| ());
+    Ok(())
 }
 
 // Can't put this in the generic impl block, otherwise we get privacy issues with the notifier type
@@ -350,11 +482,24 @@ impl Reactor<Poller> {
     pub(crate) fn notifier(&self) -> Arc<Notifier> {
         self.notifier.clone()
     }
+
+    /// List the FDs that the reactor would wait on, along with the events of interest
+    pub(crate) fn poll_fds(&self) -> Vec<(Source, bool, bool)> {
+        let state = self.state.borrow();
+        state
+            .event_sources
+            .iter()
+            .map(|(source, data)| (*source, data.filter()))
+            .filter(|(_, filter)| filter.read || filter.write)
+            .map(|(source, filter)| (source, filter.read, filter.write))
+            .chain(state.poller.internal_fds().map(|fd| (fd, true, false)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{array, sync::atomic::AtomicU32};
+    use std::{array, cell::Cell, rc::Rc, sync::atomic::AtomicU32};
 
     use crate::test::MockWaker;
 
@@ -731,4 +876,89 @@ mod tests {
         // Even if poller fails, deregistering should still delete the event from the reactor
         assert!(borrow!(reactor->event_sources.is_empty()));
     }
+
+    #[test]
+    fn metrics() {
+        let reactor = Reactor::<MockPoller>::new().unwrap();
+        let waker = Arc::new(MockWaker::default());
+        unsafe { reactor.register_event(100).unwrap() };
+        unsafe { reactor.register_event(101).unwrap() };
+        reactor.register_timer(
+            Instant::now() + Duration::from_secs(10),
+            waker.clone().into(),
+        );
+
+        borrow!(reactor->poller.poll_output = vec![(100, Filter::read()), (101, Filter::write())]);
+        reactor.wait().unwrap();
+        let metrics = reactor.metrics();
+        assert_eq!(metrics.event_sources(), 2);
+        assert_eq!(metrics.timers(), 1);
+        assert_eq!(metrics.waits(), 1);
+        assert_eq!(metrics.polls(), 1);
+        assert_eq!(metrics.events(), 2);
+
+        // Skip polling if the notifier is already set
+        reactor.notifier.notify().unwrap();
+        reactor.wait().unwrap();
+        reactor.deregister_event(101).unwrap();
+        let metrics = reactor.metrics();
+        assert_eq!(metrics.event_sources(), 1);
+        assert_eq!(metrics.waits(), 2);
+        assert_eq!(metrics.polls(), 1);
+        assert_eq!(metrics.events(), 2);
+    }
+
+    #[test]
+    fn park_hooks() {
+        let reactor = Reactor::<MockPoller>::new().unwrap();
+        let parks = Rc::new(Cell::new(0));
+        let unparks = Rc::new(Cell::new(0));
+        let (p, u) = (parks.clone(), unparks.clone());
+        reactor.set_park_hooks(
+            ParkHooks::new()
+                .before_park(move || p.set(p.get() + 1))
+                .after_unpark(move || u.set(u.get() + 1)),
+        );
+
+        reactor.wait().unwrap();
+        assert_eq!((parks.get(), unparks.get()), (1, 1));
+
+        // Don't park if the wakeup is instant
+        reactor.notifier.notify().unwrap();
+        reactor.wait().unwrap();
+        reactor.register_timer(Instant::now(), Arc::new(MockWaker::default()).into());
+        reactor.wait().unwrap();
+        assert_eq!((parks.get(), unparks.get()), (1, 1));
+
+        // Unpark hook runs even if polling fails
+        borrow!(reactor->poller.ret_error) = true;
+        assert!(reactor.wait().is_err());
+        assert_eq!((parks.get(), unparks.get()), (2, 2));
+
+        reactor.set_park_hooks(ParkHooks::new());
+        borrow!(reactor->poller.ret_error) = false;
+        reactor.wait().unwrap();
+        assert_eq!((parks.get(), unparks.get()), (2, 2));
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let reactor = Reactor::<MockPoller>::new().unwrap();
+        let waker = Arc::new(MockWaker::default());
+        unsafe { reactor.register_event(100).unwrap() };
+        reactor
+            .enable_event(100, Interest::Read, &waker.clone().into())
+            .unwrap();
+
+        // Events are collected even if the wait doesn't block
+        reactor.notifier.notify().unwrap();
+        borrow!(reactor->poller.poll_output = vec![(100, Filter::read())]);
+        reactor.wait_timeout(Some(Duration::ZERO)).unwrap();
+        assert!(waker.get());
+        assert_eq!(reactor.metrics().polls(), 1);
+
+        let expiry = Instant::now() + Duration::from_secs(10);
+        reactor.register_timer(expiry, waker.clone().into());
+        assert_eq!(reactor.next_timer_expiry(), Some(expiry));
+    }
 }
@@ -19,7 +19,9 @@ use rustix::{
 
 use crate::io::set_nonblocking;
 
-use super::{EventNotifier, EventPoller, Filter, Source, WithFlag};
+use crate::runtime::{NotifierBackend, PollerBackend, TimerBackend};
+
+use super::{EventNotifier, EventPoller, Filter, ReactorConfig, Source, WithFlag};
 
 fn read_flags() -> PollFlags {
     PollFlags::IN | PollFlags::HUP | PollFlags::ERR | PollFlags::PRI
@@ -49,6 +51,49 @@ pub(crate) struct PollPoller<N, T> {
     timeout: T,
 }
 
+#[allow(private_bounds)]
+impl<N: NotifierFd, T: Timeout> PollPoller<N, T> {
+    fn with_parts(notifier: N, timeout: T, event_capacity: usize) -> (Self, Arc<WithFlag<N>>) {
+        let notifier = Arc::new(WithFlag::new(notifier));
+        let notifier_cl = notifier.clone();
+        (
+            Self {
+                // Leave room for the notifier and timer FDs
+                pollfds: Vec::with_capacity(event_capacity + 2),
+                notifier,
+                timeout,
+            },
+            notifier_cl,
+        )
+    }
+}
+
+impl Poller {
+    /// FDs used internally by the poller, which only need to be polled for reading
+    pub(crate) fn internal_fds(&self) -> impl Iterator<Item = RawFd> {
+        std::iter::once(self.notifier.inner.as_raw_fd()).chain(self.timeout.maybe_fd())
+    }
+
+    /// Construct the poller using the backends selected in the config
+    pub(crate) fn with_config(
+        config: &ReactorConfig,
+    ) -> io::Result<(Self, Arc<WithFlag<AnyNotifier>>)> {
+        let notifier = match config.notifier {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            NotifierBackend::EventFd => AnyNotifier::EventFd(EventFd::new()?),
+            NotifierBackend::Pipe => AnyNotifier::Pipe(PipeFd::new()?),
+        };
+        let timeout = match config.timer {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            TimerBackend::TimerFd => AnyTimeout::TimerFd(TimerFd::new()?),
+            TimerBackend::PollTimeout => AnyTimeout::PollTimeout(PollTimeout),
+        };
+        match config.poller {
+            PollerBackend::Poll => Ok(Self::with_parts(notifier, timeout, config.event_capacity)),
+        }
+    }
+}
+
 impl<N: NotifierFd, T: Timeout> EventPoller for PollPoller<N, T> {
     type Notifier = N;
 
@@ -56,16 +101,7 @@ impl<N: NotifierFd, T: Timeout> EventPoller for PollPoller<N, T> {
     where
         Self: Sized,
     {
-        let notifier = Arc::new(WithFlag::new(N::new()?));
-        let notifier_cl = notifier.clone();
-        Ok((
-            Self {
-                pollfds: vec![],
-                notifier,
-                timeout: T::new()?,
-            },
-            notifier_cl,
-        ))
+        Ok(Self::with_parts(N::new()?, T::new()?, 0))
     }
 
     unsafe fn register(&mut self, _source: Source) -> io::Result<()> {
@@ -140,8 +176,9 @@ impl<N: NotifierFd, T: Timeout> EventPoller for PollPoller<N, T> {
                 None
             }
 
+            // Only report events from the event sources, not from the internal FDs
             _ => Some(
-                self.pollfds
+                self.pollfds[..event_len]
                     .iter()
                     .filter(|pollfd| !pollfd.revents().is_empty())
                     .map(|pollfd| {
@@ -204,7 +241,6 @@ impl AsRawFd for EventFd {
 }
 
 /// Unix pipe for notifying the poller on non-Linux platforms
-#[allow(unused)]
 pub(crate) struct PipeFd {
     read: OwnedFd,
     write: OwnedFd,
@@ -260,7 +296,6 @@ pub(crate) trait Timeout {
 /// Use the timeout argument of poll() to handle timers
 ///
 /// Limited to only millisecond precision
-#[allow(unused)]
 pub(crate) struct PollTimeout;
 
 impl Timeout for PollTimeout {
@@ -334,14 +369,84 @@ impl Timeout for TimerFd {
     }
 }
 
-#[cfg(any(target_os = "linux", target_os = "android"))]
-pub(crate) type Poller = PollPoller<EventFd, TimerFd>;
-#[cfg(not(any(target_os = "linux", target_os = "android")))]
-pub(crate) type Poller = PollPoller<PipeFd, PollTimeout>;
-#[cfg(any(target_os = "linux", target_os = "android"))]
-pub(crate) type PollerNotifier = EventFd;
-#[cfg(not(any(target_os = "linux", target_os = "android")))]
-pub(crate) type PollerNotifier = PipeFd;
+/// Notifier whose implementation is selected at runtime
+pub(crate) enum AnyNotifier {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    EventFd(EventFd),
+    Pipe(PipeFd),
+}
+
+impl NotifierFd for AnyNotifier {
+    fn new() -> io::Result<Self> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return EventFd::new().map(Self::EventFd);
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return PipeFd::new().map(Self::Pipe);
+    }
+}
+
+impl EventNotifier for AnyNotifier {
+    fn clear(&self) -> io::Result<()> {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::EventFd(n) => n.clear(),
+            Self::Pipe(n) => n.clear(),
+        }
+    }
+
+    fn notify(&self) -> io::Result<()> {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::EventFd(n) => n.notify(),
+            Self::Pipe(n) => n.notify(),
+        }
+    }
+}
+
+impl AsRawFd for AnyNotifier {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::EventFd(n) => n.as_raw_fd(),
+            Self::Pipe(n) => n.as_raw_fd(),
+        }
+    }
+}
+
+/// Timeout handling whose implementation is selected at runtime
+pub(crate) enum AnyTimeout {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    TimerFd(TimerFd),
+    PollTimeout(PollTimeout),
+}
+
+impl Timeout for AnyTimeout {
+    fn new() -> io::Result<Self> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return TimerFd::new().map(Self::TimerFd);
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return Ok(Self::PollTimeout(PollTimeout));
+    }
+
+    fn set_timeout(&self, duration: Option<Duration>) -> io::Result<i32> {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::TimerFd(t) => t.set_timeout(duration),
+            Self::PollTimeout(t) => t.set_timeout(duration),
+        }
+    }
+
+    fn maybe_fd(&self) -> Option<RawFd> {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::TimerFd(t) => t.maybe_fd(),
+            Self::PollTimeout(t) => t.maybe_fd(),
+        }
+    }
+}
+
+pub(crate) type Poller = PollPoller<AnyNotifier, AnyTimeout>;
+pub(crate) type PollerNotifier = AnyNotifier;
 
 #[cfg(test)]
 mod tests {
@@ -456,6 +561,35 @@ mod tests {
         pipe.clear().unwrap();
     }
 
+    #[test]
+    fn poller_with_config() {
+        let mut configs = vec![(NotifierBackend::Pipe, TimerBackend::PollTimeout)];
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        configs.extend([
+            (NotifierBackend::EventFd, TimerBackend::TimerFd),
+            (NotifierBackend::Pipe, TimerBackend::TimerFd),
+        ]);
+
+        for (notifier, timer) in configs {
+            let config = ReactorConfig {
+                poller: PollerBackend::Poll,
+                notifier,
+                timer,
+                event_capacity: 4,
+                park_hooks: Default::default(),
+            };
+            let (mut poller, notifier) = Poller::with_config(&config).unwrap();
+
+            let start = Instant::now();
+            assert_poller_wait!(poller, Some(Duration::from_millis(10))).unwrap();
+            assert!(start.elapsed() >= Duration::from_millis(10));
+
+            notifier.notify().unwrap();
+            assert_poller_wait!(poller, None).unwrap();
+            notifier.clear().unwrap();
+        }
+    }
+
     //#[cfg(target_os = "linux")]
     //#[test]
     //fn flag_notifier() {
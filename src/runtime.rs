@@ -0,0 +1,290 @@
+//! Runtime configuration
+//!
+//! By default, the reactor of each thread is created with the platform's preferred backends the
+//! first time it's used, and panics if creation fails. [`Builder`] configures the reactor of the
+//! current thread before it's first used, and also builds an [`Executor`] to go along with it.
+//!
+//! # Thread per core
+//!
+//! Each thread has its own reactor, so running the runtime on multiple threads requires an
+//! [`Executor`] per thread. [`Runtime`] launches a number of worker threads, each running its own
+//! executor, and joins them once they're done. Tasks never move between threads, but workers can
+//! send `Send` work items to each other, which are spawned as tasks once they arrive. See
+//! [`WorkerContext::spawn_balanced`].
+//!
+//! # Embedding
+//!
+//! [`block_on`](crate::block_on) takes over the thread until its future completes, which doesn't
+//! work if the thread already runs another event loop, such as a GUI loop. Instead, the host loop
+//! can wait on the FDs from [`poll_fds`] alongside its own, with a timeout based on
+//! [`next_timer_deadline`]. Whenever the reactor's FDs become ready or the deadline passes, the
+//! host loop calls [`Executor::turn`] to process the events and poll the awoken tasks.
+//!
+//! ```
+//! use std::time::{Duration, Instant};
+//! use local_runtime::{runtime, time::sleep, Executor};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let ex = Executor::new();
+//! let task = ex.spawn(async {
+//!     sleep(Duration::from_millis(10)).await;
+//!     5
+//! });
+//! // Spawned tasks run on the first turn
+//! ex.turn(Some(Duration::ZERO))?;
+//! while !task.is_finished() {
+//!     // This is where the host loop would wait on its own events, along with the FDs from
+//!     // runtime::poll_fds()
+//!     let deadline = runtime::next_timer_deadline().unwrap();
+//!     std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+//!     ex.turn(Some(Duration::ZERO))?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    io,
+    num::NonZero,
+    time::{Duration, Instant},
+};
+
+use crate::{hooks::ParkHooks, reactor, reactor::REACTOR, Executor};
+
+mod per_core;
+
+pub use per_core::{JoinError, Runtime, WorkerClosedError, WorkerContext, WorkerHandle};
+
+/// System call used by the reactor to wait for events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PollerBackend {
+    /// [`poll`](https://pubs.opengroup.org/onlinepubs/9699919799/functions/poll.html)
+    #[default]
+    Poll,
+}
+
+/// Mechanism used to wake up the reactor from other threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NotifierBackend {
+    /// Linux `eventfd`, which is the default on Linux and Android
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    EventFd,
+    /// Unix pipe, which is the default on other platforms
+    Pipe,
+}
+
+impl Default for NotifierBackend {
+    fn default() -> Self {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return Self::EventFd;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return Self::Pipe;
+    }
+}
+
+/// Mechanism used by the reactor to wait for timers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimerBackend {
+    /// Linux `timerfd`, which has nanosecond precision and is the default on Linux and Android
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    TimerFd,
+    /// Timeout argument of the poller, which only has millisecond precision and is the default on
+    /// other platforms
+    PollTimeout,
+}
+
+impl Default for TimerBackend {
+    fn default() -> Self {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return Self::TimerFd;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return Self::PollTimeout;
+    }
+}
+
+/// Configures the reactor of the current thread and builds an [`Executor`]
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use local_runtime::{runtime::{Builder, TimerBackend}, time::sleep};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let ex = Builder::new()
+///     .timer(TimerBackend::PollTimeout)
+///     .event_capacity(64)
+///     .budget(128)
+///     .build()?;
+/// ex.block_on(async {
+///     ex.spawn(sleep(Duration::from_millis(5))).await;
+/// });
+///
+/// // The reactor can only be configured once per thread
+/// assert!(Builder::new().build().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder {
+    poller: PollerBackend,
+    notifier: NotifierBackend,
+    timer: TimerBackend,
+    event_capacity: usize,
+    park_hooks: ParkHooks,
+    task_capacity: Option<usize>,
+    budget: Option<NonZero<usize>>,
+}
+
+impl Builder {
+    /// Create a builder with the platform's default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the system call used by the reactor to wait for events
+    pub fn poller(mut self, poller: PollerBackend) -> Self {
+        self.poller = poller;
+        self
+    }
+
+    /// Set the mechanism used to wake up the reactor from other threads
+    pub fn notifier(mut self, notifier: NotifierBackend) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Set the mechanism used by the reactor to wait for timers
+    pub fn timer(mut self, timer: TimerBackend) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    /// Pre-allocate space for waiting on `capacity` I/O event sources at once
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// Set the park hooks of the reactor
+    ///
+    /// See [`set_park_hooks`](crate::hooks::set_park_hooks).
+    pub fn park_hooks(mut self, hooks: ParkHooks) -> Self {
+        self.park_hooks = hooks;
+        self
+    }
+
+    /// Pre-allocate space for `capacity` tasks in the executor
+    ///
+    /// See [`Executor::with_capacity`].
+    pub fn task_capacity(mut self, capacity: usize) -> Self {
+        self.task_capacity = Some(capacity);
+        self
+    }
+
+    /// Set the max number of task wakeups the executor processes each time it's polled
+    ///
+    /// Once the budget runs out, the executor yields back to [`block_on`](crate::block_on) so
+    /// that other futures get a chance to run, even if there are still tasks to poll. The
+    /// remaining tasks are polled the next time the executor is polled. By default, there is no
+    /// budget.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `budget` is 0.
+    pub fn budget(mut self, budget: usize) -> Self {
+        self.budget = Some(NonZero::new(budget).expect("budget must be non-zero"));
+        self
+    }
+
+    /// Initialize the reactor of the current thread and build an [`Executor`]
+    ///
+    /// Returns an error if the reactor fails to initialize, or if the current thread's reactor
+    /// has already been initialized. The reactor is initialized the first time any I/O object or
+    /// timer is used, or when [`block_on`](crate::block_on) is called.
+    pub fn build<'a>(self) -> io::Result<Executor<'a>> {
+        reactor::install(reactor::ReactorConfig {
+            poller: self.poller,
+            notifier: self.notifier,
+            timer: self.timer,
+            event_capacity: self.event_capacity,
+            park_hooks: self.park_hooks,
+        })?;
+        let mut ex = match self.task_capacity {
+            Some(capacity) => Executor::with_capacity(capacity),
+            None => Executor::new(),
+        };
+        if let Some(budget) = self.budget {
+            ex.budget = budget.get();
+        }
+        Ok(ex)
+    }
+}
+
+/// Run one iteration of the current thread's reactor
+///
+/// Waits for I/O events until the next timer expires, or until `timeout` elapses, whichever comes
+/// first. Then wakes up all futures whose events have occurred or whose timers have expired. If
+/// `timeout` is `None`, there is no limit on the wait time other than the timers. If `timeout` is
+/// zero, the reactor doesn't block.
+///
+/// This function doesn't poll any futures. To also poll the tasks of an executor, use
+/// [`Executor::turn`].
+pub fn turn(timeout: Option<Duration>) -> io::Result<()> {
+    REACTOR.with(|r| r.wait_timeout(timeout))
+}
+
+/// Get the expiry time of the current thread's earliest timer, if there is one
+pub fn next_timer_deadline() -> Option<Instant> {
+    REACTOR.with(|r| r.next_timer_expiry())
+}
+
+/// A file descriptor that the reactor waits on
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollFd {
+    fd: std::os::fd::RawFd,
+    readable: bool,
+    writable: bool,
+}
+
+#[cfg(unix)]
+impl PollFd {
+    /// The file descriptor
+    pub fn fd(&self) -> std::os::fd::RawFd {
+        self.fd
+    }
+
+    /// Whether the reactor waits for the file descriptor to be readable
+    pub fn readable(&self) -> bool {
+        self.readable
+    }
+
+    /// Whether the reactor waits for the file descriptor to be writable
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+}
+
+/// List the file descriptors that the current thread's reactor waits on
+///
+/// When any of these FDs are ready, the reactor needs to run to process the event. This includes
+/// the reactor's internal FDs, such as the one used to wake up the reactor from other threads.
+///
+/// The list changes whenever an I/O object registers interest in new events, so this should be
+/// called again before every wait.
+#[cfg(unix)]
+pub fn poll_fds() -> Vec<PollFd> {
+    REACTOR
+        .with(|r| r.poll_fds())
+        .into_iter()
+        .map(|(fd, readable, writable)| PollFd {
+            fd,
+            readable,
+            writable,
+        })
+        .collect()
+}
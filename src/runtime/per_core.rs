@@ -0,0 +1,519 @@
+use std::{
+    any::Any,
+    fmt::{Debug, Display},
+    future::{poll_fn, Future},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::Poll,
+    thread,
+};
+
+use atomic_waker::AtomicWaker;
+use concurrent_queue::{ConcurrentQueue, PushError};
+use futures_core::future::LocalBoxFuture;
+
+use crate::{cancel::CancellationToken, spawn_local, Executor};
+
+/// Launcher for running one [`Executor`] on each of several threads
+///
+/// Each worker thread has its own reactor and executor, and runs a future created by a shared
+/// closure. Workers can signal each other across threads with [`WorkerHandle`]s. Pair this with
+/// [`Async::<TcpListener>::bind_reuseport`](crate::io::Async::bind_reuseport) to have every
+/// worker accept connections on the same port.
+///
+/// # Example
+///
+/// ```
+/// use local_runtime::runtime::Runtime;
+///
+/// let results = Runtime::new()
+///     .thread_name("worker")
+///     .launch(4, |ctx| async move {
+///         // The first worker tells every worker to shut down
+///         if ctx.index() == 0 {
+///             ctx.shutdown_all();
+///         }
+///         ctx.shutdown_token().cancelled().await;
+///         ctx.index() * 10
+///     })
+///     .unwrap();
+/// assert_eq!(results, [0, 10, 20, 30]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Runtime {
+    pin_cores: bool,
+    thread_name: Option<String>,
+}
+
+impl Runtime {
+    /// Create a launcher with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin each worker thread to a different CPU core
+    ///
+    /// Workers are assigned to the cores that the process is allowed to run on, in order. If
+    /// there are more workers than cores, the assignment wraps around. Pinning is only supported
+    /// on Linux and Android, and is ignored on other platforms. Failure to pin a thread is logged
+    /// but otherwise ignored.
+    pub fn pin_cores(mut self, pin_cores: bool) -> Self {
+        self.pin_cores = pin_cores;
+        self
+    }
+
+    /// Name the worker threads, with the index of each worker appended to the name
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = Some(name.into());
+        self
+    }
+
+    /// Run `threads` workers with default settings
+    ///
+    /// This is just a shorthand for `Runtime::new().launch(threads, f)`.
+    pub fn per_core<F, Fut, T>(threads: usize, f: F) -> Result<Vec<T>, JoinError>
+    where
+        F: Fn(WorkerContext) -> Fut + Sync,
+        Fut: Future<Output = T>,
+        T: Send,
+    {
+        Self::new().launch(threads, f)
+    }
+
+    /// Run `threads` workers, blocking until all of them finish
+    ///
+    /// Each worker thread calls `f` to create a future, then drives it to completion on the
+    /// thread's own [`Executor`], which is available through [`WorkerContext::executor`]. Like
+    /// [`Executor::block_on`], unfinished tasks are dropped once the future completes.
+    ///
+    /// Returns the outputs of the workers in order of their indices. If any of the workers
+    /// panic, then all other workers are told to [shut down](WorkerHandle::shutdown), and the
+    /// panics are returned as a [`JoinError`] once all workers finish.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `threads` is 0, or if a thread fails to spawn.
+    pub fn launch<F, Fut, T>(self, threads: usize, f: F) -> Result<Vec<T>, JoinError>
+    where
+        F: Fn(WorkerContext) -> Fut + Sync,
+        Fut: Future<Output = T>,
+        T: Send,
+    {
+        assert!(threads > 0, "number of worker threads must be non-zero");
+        let handles: Arc<[WorkerHandle]> = (0..threads)
+            .map(|index| WorkerHandle {
+                index,
+                shared: Arc::default(),
+            })
+            .collect();
+        let cores = if self.pin_cores {
+            allowed_cores()
+        } else {
+            vec![]
+        };
+
+        let f = &f;
+        let results: Vec<_> = thread::scope(|s| {
+            let threads: Vec<_> = (0..threads)
+                .map(|index| {
+                    let mut builder = thread::Builder::new();
+                    if let Some(name) = &self.thread_name {
+                        builder = builder.name(format!("{name}-{index}"));
+                    }
+                    let handles = handles.clone();
+                    let core = (!cores.is_empty()).then(|| cores[index % cores.len()]);
+                    builder
+                        .spawn_scoped(s, move || {
+                            if let Some(core) = core {
+                                pin_to_core(core);
+                            }
+                            run_worker(index, handles, f)
+                        })
+                        .expect("failed to spawn worker thread")
+                })
+                .collect();
+            // Join all threads manually, so that the scope doesn't panic
+            threads.into_iter().map(|t| t.join()).collect()
+        });
+
+        let mut outputs = Vec::with_capacity(results.len());
+        let mut panics = vec![];
+        for (index, res) in results.into_iter().enumerate() {
+            match res {
+                Ok(out) => outputs.push(out),
+                Err(payload) => panics.push((index, payload)),
+            }
+        }
+        if panics.is_empty() {
+            Ok(outputs)
+        } else {
+            Err(JoinError { panics })
+        }
+    }
+}
+
+fn run_worker<F, Fut, T>(index: usize, handles: Arc<[WorkerHandle]>, f: &F) -> T
+where
+    F: Fn(WorkerContext) -> Fut,
+    Fut: Future<Output = T>,
+{
+    let _guard = WorkerGuard {
+        index,
+        handles: handles.clone(),
+    };
+    let ex = Rc::new(Executor::new());
+    let shutdown = CancellationToken::new();
+
+    // Spawn the work items and forward the shutdown requests from other threads. The wakeups from
+    // other threads go through the executor's concurrent wakeup queue.
+    let shared = handles[index].shared.clone();
+    let token = shutdown.clone();
+    ex.spawn(poll_fn(move |cx| {
+        shared.waker.register(cx.waker());
+        while let Ok(item) = shared.work.pop() {
+            let guard = LoadGuard(shared.clone());
+            spawn_local(async move {
+                let _guard = guard;
+                item().await;
+            });
+        }
+        if shared.shutdown.load(Ordering::Acquire) {
+            token.cancel();
+        }
+        Poll::<()>::Pending
+    }));
+
+    let shared = handles[index].shared.clone();
+    let ctx = WorkerContext {
+        index,
+        ex: ex.clone(),
+        handles,
+        shutdown,
+    };
+    ex.block_on(async move {
+        let out = f(ctx).await;
+        // Stop accepting work items before block_on returns, since they would never be spawned
+        shared.work.close();
+        out
+    })
+}
+
+struct WorkerGuard {
+    index: usize,
+    handles: Arc<[WorkerHandle]>,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        // Stop accepting work items if the worker's future panicked
+        self.handles[self.index].shared.work.close();
+        // If this worker panics, shut down the others so that they can be joined
+        if thread::panicking() {
+            for handle in self.handles.iter() {
+                handle.shutdown();
+            }
+        }
+    }
+}
+
+// Decrements the load of a worker when a work item finishes or is dropped
+struct LoadGuard(Arc<WorkerShared>);
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        self.0.load.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn allowed_cores() -> Vec<usize> {
+    use rustix::process::{sched_getaffinity, CpuSet};
+
+    match sched_getaffinity(None) {
+        Ok(set) => (0..CpuSet::MAX_CPU).filter(|&c| set.is_set(c)).collect(),
+        Err(err) => {
+            log::error!("Failed to get CPU affinity: {err}");
+            vec![]
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn allowed_cores() -> Vec<usize> {
+    vec![]
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn pin_to_core(core: usize) {
+    use rustix::process::{sched_setaffinity, CpuSet};
+
+    let mut set = CpuSet::new();
+    set.set(core);
+    if let Err(err) = sched_setaffinity(None, &set) {
+        log::error!(
+            "{:?} Failed to pin thread to core {core}: {err}",
+            thread::current().id()
+        );
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn pin_to_core(_core: usize) {}
+
+type WorkItem = Box<dyn FnOnce() -> LocalBoxFuture<'static, ()> + Send>;
+
+struct WorkerShared {
+    shutdown: AtomicBool,
+    waker: AtomicWaker,
+    work: ConcurrentQueue<WorkItem>,
+    // Number of work items that are queued or running on the worker
+    load: AtomicUsize,
+}
+
+impl Default for WorkerShared {
+    fn default() -> Self {
+        Self {
+            shutdown: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+            work: ConcurrentQueue::unbounded(),
+            load: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Handle to a worker thread, which can be sent to other threads
+#[derive(Clone)]
+pub struct WorkerHandle {
+    index: usize,
+    shared: Arc<WorkerShared>,
+}
+
+impl Debug for WorkerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerHandle")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl WorkerHandle {
+    /// Index of the worker
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Ask the worker to shut down by cancelling its
+    /// [shutdown token](WorkerContext::shutdown_token)
+    ///
+    /// It's up to the worker's future to wait on the token and finish. This can be called from
+    /// any thread.
+    pub fn shutdown(&self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.shared.waker.wake();
+    }
+
+    /// Number of work items that are queued or running on the worker
+    ///
+    /// Only work items sent with [`WorkerHandle::spawn`] or [`WorkerContext::spawn_balanced`] are
+    /// counted. Tasks spawned directly on the worker's executor aren't.
+    pub fn load(&self) -> usize {
+        self.shared.load.load(Ordering::Relaxed)
+    }
+
+    /// Send a work item to the worker, which spawns the future returned by `f` as a task
+    ///
+    /// Only the closure needs to be `Send`, since it's called on the worker's thread to create the
+    /// future. Returns an error if the worker's future has already completed. Work items that are
+    /// accepted before then, but haven't finished when the future completes, are dropped along
+    /// with the worker's other unfinished tasks.
+    pub fn spawn<F, Fut>(&self, f: F) -> Result<(), WorkerClosedError>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        self.push(Box::new(move || Box::pin(f())))
+            .map_err(|_| WorkerClosedError)
+    }
+
+    fn push(&self, item: WorkItem) -> Result<(), WorkItem> {
+        self.shared.load.fetch_add(1, Ordering::Relaxed);
+        if let Err(err) = self.shared.work.push(item) {
+            self.shared.load.fetch_sub(1, Ordering::Relaxed);
+            return Err(match err {
+                PushError::Full(item) | PushError::Closed(item) => item,
+            });
+        }
+        self.shared.waker.wake();
+        Ok(())
+    }
+}
+
+/// Error returned by [`WorkerHandle::spawn`] when the worker has already finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerClosedError;
+
+impl Display for WorkerClosedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("worker has already finished")
+    }
+}
+
+impl std::error::Error for WorkerClosedError {}
+
+/// Context passed to each worker by [`Runtime::launch`]
+pub struct WorkerContext {
+    index: usize,
+    ex: Rc<Executor<'static>>,
+    handles: Arc<[WorkerHandle]>,
+    shutdown: CancellationToken,
+}
+
+impl Debug for WorkerContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerContext")
+            .field("index", &self.index)
+            .field("workers", &self.handles.len())
+            .finish()
+    }
+}
+
+impl WorkerContext {
+    /// Index of the current worker, which ranges from 0 to the number of workers, exclusive
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Executor of the current worker thread
+    ///
+    /// The worker's future is run on this executor, so [`spawn_local`](crate::spawn_local) also
+    /// spawns onto it.
+    pub fn executor(&self) -> &Rc<Executor<'static>> {
+        &self.ex
+    }
+
+    /// Handles to all workers, including the current one, ordered by index
+    pub fn handles(&self) -> &[WorkerHandle] {
+        &self.handles
+    }
+
+    /// Token that's cancelled when the current worker is asked to shut down
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Send a work item to the least-loaded worker, returning the index of that worker
+    ///
+    /// The load of each worker is its number of queued or running work items, as reported by
+    /// [`WorkerHandle::load`]. Ties are broken in favour of the current worker, and then the
+    /// worker with the lowest index. The worker spawns the future returned by `f` as a task on its
+    /// own executor, so only the closure needs to be `Send`. Once spawned, the task stays on that
+    /// worker.
+    ///
+    /// Work items are only distributed when they're sent with this method or with
+    /// [`WorkerHandle::spawn`]. Tasks spawned directly on an executor never move between threads.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use local_runtime::runtime::Runtime;
+    ///
+    /// let (tx, rx) = flume::unbounded();
+    /// let (release_tx, release_rx) = flume::unbounded();
+    /// Runtime::per_core(2, |ctx| {
+    ///     let tx = tx.clone();
+    ///     let rx = rx.clone();
+    ///     let release_tx = release_tx.clone();
+    ///     let release_rx = release_rx.clone();
+    ///     async move {
+    ///         if ctx.index() == 0 {
+    ///             let mut workers = vec![];
+    ///             for _ in 0..4 {
+    ///                 let tx = tx.clone();
+    ///                 let release_rx = release_rx.clone();
+    ///                 // Work items stay running until they're released, so the loads only go up
+    ///                 workers.push(ctx.spawn_balanced(move || async move {
+    ///                     release_rx.recv_async().await.unwrap();
+    ///                     tx.send(()).unwrap();
+    ///                 }));
+    ///             }
+    ///             assert_eq!(workers, [0, 1, 0, 1]);
+    ///             for _ in 0..4 {
+    ///                 release_tx.send(()).unwrap();
+    ///             }
+    ///             for _ in 0..4 {
+    ///                 rx.recv_async().await.unwrap();
+    ///             }
+    ///             ctx.shutdown_all();
+    ///         }
+    ///         ctx.shutdown_token().cancelled().await;
+    ///     }
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn spawn_balanced<F, Fut>(&self, f: F) -> usize
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let mut item: WorkItem = Box::new(move || Box::pin(f()));
+        loop {
+            let target = self
+                .handles
+                .iter()
+                .filter(|h| !h.shared.work.is_closed())
+                .min_by_key(|h| (h.load(), h.index != self.index))
+                // The current worker's queue is open while it's running
+                .expect("no open workers");
+            match target.push(item) {
+                Ok(()) => return target.index,
+                // The worker finished in the meantime, so try another one
+                Err(ret) => item = ret,
+            }
+        }
+    }
+
+    /// Ask all workers to shut down, including the current one
+    pub fn shutdown_all(&self) {
+        for handle in self.handles.iter() {
+            handle.shutdown();
+        }
+    }
+}
+
+/// Error returned by [`Runtime::launch`] when any of the workers panic
+pub struct JoinError {
+    panics: Vec<(usize, Box<dyn Any + Send + 'static>)>,
+}
+
+impl JoinError {
+    /// Indices of the workers that panicked
+    pub fn workers(&self) -> impl Iterator<Item = usize> + '_ {
+        self.panics.iter().map(|(index, _)| *index)
+    }
+
+    /// Get the panic payloads, along with the indices of the workers that panicked
+    ///
+    /// The payloads can be passed to [`std::panic::resume_unwind`] to propagate the panics.
+    pub fn into_panics(self) -> Vec<(usize, Box<dyn Any + Send + 'static>)> {
+        self.panics
+    }
+}
+
+impl Debug for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JoinError")
+            .field("workers", &self.workers().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} worker thread(s) panicked", self.panics.len())
+    }
+}
+
+impl std::error::Error for JoinError {}
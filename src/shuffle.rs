@@ -0,0 +1,109 @@
+use std::cell::Cell;
+
+// Randomizes the scheduling decisions of an executor for testing
+pub(crate) struct Shuffler {
+    seed: u64,
+    state: Cell<u64>,
+    // Chance of waking up a pending task after it's polled, out of `u64::MAX`
+    spurious_threshold: u64,
+}
+
+impl Shuffler {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: Cell::new(seed),
+            spurious_threshold: 0,
+        }
+    }
+
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub(crate) fn set_spurious_wakeups(&mut self, probability: f64) {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability must be between 0 and 1"
+        );
+        self.spurious_threshold = (probability * u64::MAX as f64) as u64;
+    }
+
+    // SplitMix64, which is tiny and has good enough statistical properties for scheduling
+    fn next(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9e3779b97f4a7c15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Random index in 0..len
+    pub(crate) fn index(&self, len: usize) -> usize {
+        // The modulo bias is negligible for the small lengths we deal with
+        (self.next() % len as u64) as usize
+    }
+
+    pub(crate) fn shuffle<T>(&self, slice: &mut [T]) {
+        // Fisher-Yates
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.index(i + 1));
+        }
+    }
+
+    pub(crate) fn spurious_wakeup(&self) -> bool {
+        self.spurious_threshold > 0 && self.next() <= self.spurious_threshold
+    }
+}
+
+// Prints the seed if the executor panics, so that the failing interleaving can be replayed
+pub(crate) struct SeedGuard(pub(crate) Option<u64>);
+
+impl Drop for SeedGuard {
+    fn drop(&mut self) {
+        if let Some(seed) = self.0 {
+            if std::thread::panicking() {
+                eprintln!(
+                    "local_runtime: executor panicked with shuffle seed {seed}, replay with \
+                     `Executor::new().shuffle({seed})`"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let a = Shuffler::new(42);
+        let b = Shuffler::new(42);
+        let mut va: Vec<_> = (0..20).collect();
+        let mut vb = va.clone();
+        a.shuffle(&mut va);
+        b.shuffle(&mut vb);
+        assert_eq!(va, vb);
+        assert_ne!(va, (0..20).collect::<Vec<_>>());
+        va.sort();
+        assert_eq!(va, (0..20).collect::<Vec<_>>());
+
+        let c = Shuffler::new(43);
+        let mut vc: Vec<_> = (0..20).collect();
+        c.shuffle(&mut vc);
+        assert_ne!(vb, vc);
+    }
+
+    #[test]
+    fn spurious_wakeups() {
+        let mut shuffler = Shuffler::new(0);
+        assert!((0..100).all(|_| !shuffler.spurious_wakeup()));
+        shuffler.set_spurious_wakeups(1.0);
+        assert!((0..100).all(|_| shuffler.spurious_wakeup()));
+        shuffler.set_spurious_wakeups(0.5);
+        let count = (0..1000).filter(|_| shuffler.spurious_wakeup()).count();
+        assert!((400..600).contains(&count));
+    }
+}
@@ -0,0 +1,190 @@
+//! Task identification and introspection
+//!
+//! Every task spawned on an [`Executor`] gets a unique [`TaskId`], which can be retrieved from
+//! inside the task with [`id`]. Tasks can also be given a name and a [`Priority`] with
+//! [`Builder`]. Both the name and ID show up in [`Executor::dump`], which is useful for finding
+//! out which tasks are stuck.
+
+use std::{
+    cell::Cell,
+    fmt::Display,
+    future::Future,
+    num::NonZero,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Executor, Priority, TaskHandle};
+
+/// Unique identifier of a task
+///
+/// IDs are unique across all executors in the process, and are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(NonZero<u64>);
+
+impl TaskId {
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        TaskId(NonZero::new(id).expect("task ID overflowed"))
+    }
+
+    /// Get the numeric value of the ID
+    pub fn as_u64(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl Display for TaskId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+thread_local! {
+    // ID of the task that's currently being polled on this thread
+    static CURRENT_TASK: Cell<Option<TaskId>> = const { Cell::new(None) };
+}
+
+// Sets the current task ID, restoring the previous one on drop
+pub(crate) struct CurrentTask(Option<TaskId>);
+
+impl CurrentTask {
+    pub(crate) fn enter(id: TaskId) -> Self {
+        Self(CURRENT_TASK.with(|c| c.replace(Some(id))))
+    }
+}
+
+impl Drop for CurrentTask {
+    fn drop(&mut self) {
+        CURRENT_TASK.with(|c| c.set(self.0));
+    }
+}
+
+/// Get the ID of the task that's currently running
+///
+/// # Panic
+///
+/// Panics if not called from inside a task. Note that the future passed to [`Executor::run`]
+/// is not a task. See [`try_id`] for a non-panicking version.
+///
+/// # Example
+///
+/// ```
+/// use local_runtime::{task, Executor};
+///
+/// let ex = Executor::new();
+/// ex.block_on(async {
+///     let handle = ex.spawn(async { task::id() });
+///     let id = handle.id();
+///     assert_eq!(handle.await, id);
+/// });
+/// ```
+pub fn id() -> TaskId {
+    try_id().expect("task::id called outside of a task")
+}
+
+/// Get the ID of the task that's currently running, or `None` if not called from inside a task
+pub fn try_id() -> Option<TaskId> {
+    CURRENT_TASK.with(Cell::get)
+}
+
+/// Configures a task before spawning it
+///
+/// # Example
+///
+/// ```
+/// use local_runtime::{task, Executor, Priority};
+///
+/// let ex = Executor::new();
+/// ex.block_on(async {
+///     let handle = task::Builder::new()
+///         .name("health-check")
+///         .priority(Priority::High)
+///         .spawn(&ex, async { 5 });
+///     assert_eq!(ex.dump()[0].name(), Some("health-check"));
+///     assert_eq!(handle.await, 5);
+/// });
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Builder {
+    pub(crate) name: Option<String>,
+    pub(crate) priority: Priority,
+}
+
+impl Builder {
+    /// Create a builder with no name and [`Priority::Normal`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the task
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the scheduling priority of the task
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Spawn the task on an executor, returning a [`TaskHandle`] to it
+    pub fn spawn<'a, T: 'a>(
+        self,
+        ex: &Executor<'a>,
+        fut: impl Future<Output = T> + 'a,
+    ) -> TaskHandle<T> {
+        ex.spawn_with_builder(self, fut)
+    }
+}
+
+/// Snapshot of a live task, returned by [`Executor::dump`]
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub(crate) id: TaskId,
+    pub(crate) name: Option<String>,
+    pub(crate) priority: Priority,
+    pub(crate) poll_count: u64,
+    pub(crate) since_last_poll: Option<Duration>,
+    pub(crate) queued: bool,
+}
+
+impl TaskInfo {
+    /// ID of the task
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Name of the task, if it has one
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Scheduling priority of the task
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Number of times the task has been polled
+    pub fn poll_count(&self) -> u64 {
+        self.poll_count
+    }
+
+    /// Time elapsed since the task was last polled
+    ///
+    /// Returns `None` if the task has never been polled, or if the executor doesn't track poll
+    /// times. See [`Executor::track_poll_times`](crate::Executor::track_poll_times).
+    pub fn since_last_poll(&self) -> Option<Duration> {
+        self.since_last_poll
+    }
+
+    /// Whether the task has been awoken and is waiting to be polled
+    ///
+    /// If a task is not queued and hasn't been polled in a while, then it's waiting on something
+    /// that hasn't happened yet.
+    pub fn is_queued(&self) -> bool {
+        self.queued
+    }
+}
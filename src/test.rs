@@ -16,6 +16,7 @@ impl Wake for MockWaker {
 }
 
 impl MockWaker {
+    #[allow(dead_code)]
     pub fn set(&self, b: bool) {
         self.0.store(b, Ordering::Relaxed);
     }
@@ -7,8 +7,17 @@
 //! There's a limit on the precision of the timers, depending on the platform. For example, on
 //! Unix platforms without `timerfd` support, the maximum precision is 1 millisecond. This can lead
 //! to the timer sleeping for longer than the requested duration, but it will never sleep for less.
+//!
+//! # Paused time
+//!
+//! For testing, the clock of the current thread can be [paused](pause). While paused, time only
+//! moves forward through [`advance`], or when the runtime has nothing left to do except wait for
+//! a timer, in which case the clock jumps straight to the timer's expiry. This makes tests that
+//! use long timeouts run instantly and deterministically. Use [`now`] instead of
+//! [`Instant::now`] to read the clock.
 
 use std::{
+    cell::Cell,
     collections::BTreeMap,
     error::Error,
     fmt::Display,
@@ -25,6 +34,71 @@ use pin_project_lite::pin_project;
 
 use crate::{Id, REACTOR};
 
+thread_local! {
+    // Current time of the paused clock, or `None` if the clock is running
+    static PAUSED_AT: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Current time according to the runtime's clock
+///
+/// This is the same as [`Instant::now`], unless the clock is [paused](pause).
+pub fn now() -> Instant {
+    PAUSED_AT.get().unwrap_or_else(Instant::now)
+}
+
+/// Pause the clock of the current thread
+///
+/// Timers on the current thread will only expire after the clock is [advanced](advance), or
+/// when the runtime has nothing else to do. Does nothing if the clock is already paused.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use local_runtime::{block_on, time::{self, sleep}};
+///
+/// time::pause();
+/// let start = time::now();
+/// let real_start = Instant::now();
+/// block_on(sleep(Duration::from_secs(60 * 60)));
+/// assert_eq!(time::now() - start, Duration::from_secs(60 * 60));
+/// assert!(real_start.elapsed() < Duration::from_secs(60));
+/// ```
+pub fn pause() {
+    if PAUSED_AT.get().is_none() {
+        PAUSED_AT.set(Some(Instant::now()));
+    }
+}
+
+/// Resume the clock of the current thread
+///
+/// The clock jumps back to the real time. Does nothing if the clock isn't paused.
+///
+/// If the clock has been [advanced](advance), the real time is earlier than the paused time, so
+/// [`now`] moves backwards. Timers that are still pending, such as ones created with
+/// [`Timer::delay`] while the clock was paused, keep their expiry on the advanced clock, so they
+/// expire later than expected.
+pub fn resume() {
+    PAUSED_AT.set(None);
+}
+
+/// Check if the clock of the current thread is paused
+pub fn is_paused() -> bool {
+    PAUSED_AT.get().is_some()
+}
+
+/// Move the paused clock forward by `duration`
+///
+/// Timers that expire as a result are awoken the next time the reactor runs.
+///
+/// # Panic
+///
+/// Panics if the clock isn't paused.
+pub fn advance(duration: Duration) {
+    let paused_at = PAUSED_AT.get().expect("clock must be paused to advance it");
+    PAUSED_AT.set(Some(paused_at + duration));
+}
+
 pub(crate) struct TimerQueue {
     current_id: Id,
     // Each timer is identified by its expiry time and an incrementing ID, and ordered by the
@@ -76,19 +150,27 @@ impl TimerQueue {
         self.timers.remove(&(expiry, id));
     }
 
+    pub(crate) fn next_expiry(&self) -> Option<Instant> {
+        self.timers
+            .first_key_value()
+            .map(|((expiry, _), _)| *expiry)
+    }
+
     pub(crate) fn next_timeout(&mut self) -> Option<Duration> {
-        let now = Instant::now();
+        let now = now();
         self.timers
             .first_key_value()
             .map(|((expiry, _), _)| expiry.saturating_duration_since(now))
     }
 
     pub(crate) fn clear_expired(&mut self) {
-        let now = Instant::now();
+        let now = now();
         // Remove all expired timer entries and invoke their wakers
         while let Some(entry) = self.timers.first_entry() {
             let expiry = entry.key().0;
             if expiry <= now {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(late_by = ?now - expiry, "timer fired");
                 entry.remove().wake();
             } else {
                 break;
@@ -96,7 +178,10 @@ impl TimerQueue {
         }
     }
 
-    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.timers.len()
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.timers.is_empty()
     }
@@ -141,7 +226,7 @@ impl Timer {
 
     /// Timer that expires after a set duration
     pub fn delay(delay: Duration) -> Self {
-        Self::at(Instant::now() + delay)
+        Self::at(now() + delay)
     }
 
     fn register(&mut self, cx: &mut Context<'_>) {
@@ -158,7 +243,7 @@ impl Future for Timer {
     type Output = Instant;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.expiry <= Instant::now() {
+        if self.expiry <= now() {
             // Deregister the timer to prevent the waker from being called
             if let Some(id) = self.timer_id.take() {
                 REACTOR.with(|r| r.cancel_timer(id, self.expiry));
@@ -448,6 +533,55 @@ mod tests {
         assert!(REACTOR.with(|r| r.is_empty()));
     }
 
+    #[test]
+    fn paused() {
+        let waker = Arc::new(MockWaker::default());
+        pause();
+        let start = now();
+        let mut timer = pin!(Timer::delay(Duration::from_secs(10)));
+        assert!(timer
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.clone().into()))
+            .is_pending());
+
+        // The clock only moves when advanced
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(now(), start);
+        advance(Duration::from_secs(5));
+        assert!(timer
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.clone().into()))
+            .is_pending());
+
+        // With no I/O to wait on, the reactor jumps the clock to the timer's expiry
+        REACTOR.with(|r| r.wait()).unwrap();
+        assert!(waker.get());
+        assert_eq!(now(), start + Duration::from_secs(10));
+        assert!(timer
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker.into()))
+            .is_ready());
+
+        resume();
+        assert!(!is_paused());
+        assert!(now() >= start);
+    }
+
+    #[test]
+    fn advance_resume() {
+        pause();
+        let start = now();
+        advance(Duration::from_secs(3600));
+        let timer = Timer::delay(Duration::from_secs(1));
+        assert_eq!(timer.expiry, start + Duration::from_secs(3601));
+
+        // The clock goes back to the real time, which is behind the advanced clock
+        resume();
+        assert!(now() >= start);
+        assert!(now() < start + Duration::from_secs(3600));
+        assert!(timer.expiry > now() + Duration::from_secs(3599));
+    }
+
     #[test]
     fn periodic() {
         let waker = Arc::new(MockWaker::default());
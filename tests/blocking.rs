@@ -0,0 +1,31 @@
+use std::{
+    cell::Cell,
+    thread,
+    time::{Duration, Instant},
+};
+
+use local_runtime::{spawn_blocking, time::sleep, Executor};
+
+#[test]
+fn blocking_with_tasks() {
+    let ticks = Cell::new(0);
+    let start = Instant::now();
+    let ex = Executor::new();
+    let out = ex.block_on(async {
+        let _bg = ex.spawn(async {
+            loop {
+                sleep(Duration::from_millis(10)).await;
+                ticks.set(ticks.get() + 1);
+            }
+        });
+        // The blocking call shouldn't prevent the background task from running
+        spawn_blocking(|| {
+            thread::sleep(Duration::from_millis(55));
+            thread::current().id()
+        })
+        .await
+    });
+    assert_ne!(out, thread::current().id());
+    assert!(ticks.get() >= 4);
+    assert!(start.elapsed() >= Duration::from_millis(55));
+}
@@ -1,5 +1,5 @@
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     future::pending,
     net::{TcpListener, TcpStream},
     rc::Rc,
@@ -10,8 +10,9 @@ use std::{
 use futures_lite::{AsyncReadExt, AsyncWriteExt, StreamExt};
 use local_runtime::{
     io::Async,
+    spawn_local, task,
     time::{sleep, timeout, Periodic},
-    Executor,
+    try_spawn_local, Executor, NoExecutorError,
 };
 
 #[test]
@@ -247,3 +248,278 @@ fn notify_from_other_thread() {
     assert!(elapsed < Duration::from_millis(100));
     th.join().unwrap();
 }
+
+#[test]
+fn scope_waits_for_tasks() {
+    let start = Instant::now();
+    let mut results = vec![];
+    let cell = &Cell::new(0);
+    let out = local_runtime::block_on(Executor::scope(|s| async move {
+        for i in 1..=3 {
+            let s2 = s.clone();
+            s.spawn(async move {
+                sleep(Duration::from_millis(i * 10)).await;
+                cell.set(cell.get() + 1);
+                // Nested tasks are also waited on
+                s2.spawn(async move {
+                    sleep(Duration::from_millis(10)).await;
+                    cell.set(cell.get() + 1);
+                });
+            });
+        }
+        let handle = s.spawn(async { 5 });
+        handle.await
+    }));
+    assert_eq!(out, 5);
+    assert_eq!(cell.get(), 6);
+    assert!(start.elapsed() >= Duration::from_millis(40));
+
+    // Tasks can mutably borrow from the enclosing frame
+    let results_ref = &mut results;
+    local_runtime::block_on(Executor::scope(|s| async move {
+        s.spawn(async move {
+            sleep(Duration::from_millis(1)).await;
+            results_ref.push(1);
+        });
+    }));
+    assert_eq!(results, [1]);
+}
+
+#[test]
+fn try_scope() {
+    struct DropFlag<'a>(&'a Cell<bool>);
+    impl Drop for DropFlag<'_> {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let dropped = &Cell::new(false);
+    let res: Result<(), &str> = local_runtime::block_on(Executor::try_scope(|s| async move {
+        let flag = DropFlag(dropped);
+        s.spawn(async move {
+            let _flag = flag;
+            pending::<()>().await;
+        });
+        sleep(Duration::from_millis(5)).await;
+        Err("error")
+    }));
+    assert_eq!(res, Err("error"));
+    // The unfinished task should have been cancelled
+    assert!(dropped.get());
+
+    let res: Result<i32, ()> = local_runtime::block_on(Executor::try_scope(|s| async move {
+        let task = s.spawn(async {
+            sleep(Duration::from_millis(5)).await;
+            Ok(3)
+        });
+        task.await
+    }));
+    assert_eq!(res, Ok(3));
+}
+
+#[test]
+fn scope_cancelled() {
+    let cell = &Cell::new(0);
+    let ex = Executor::new();
+    ex.block_on(async {
+        let scope = Executor::scope(|s| async move {
+            s.spawn(async move {
+                sleep(Duration::from_millis(100)).await;
+                cell.set(1);
+            });
+        });
+        // Dropping the scope future drops its tasks
+        assert!(timeout(scope, Duration::from_millis(10)).await.is_err());
+    });
+    assert_eq!(cell.get(), 0);
+}
+
+#[test]
+fn spawn_local_current() {
+    assert_eq!(try_spawn_local(async {}).map(drop), Err(NoExecutorError));
+
+    let outer = Executor::new();
+    let out = outer.block_on(async {
+        let inner = Executor::new();
+        let inner_count = Rc::new(Cell::new(0));
+        let count = inner_count.clone();
+        inner
+            .run(async move {
+                // Spawns onto the inner executor, which drops the task when it finishes
+                spawn_local(async move {
+                    count.set(1);
+                    pending::<()>().await;
+                });
+                sleep(Duration::from_millis(5)).await;
+            })
+            .await;
+        assert_eq!(inner_count.get(), 1);
+        assert_eq!(Rc::strong_count(&inner_count), 1);
+
+        // After the nested run, the outer executor is current again
+        let handle = spawn_local(async {
+            sleep(Duration::from_millis(5)).await;
+            spawn_local(async { 7 }).await
+        });
+        handle.await
+    });
+    assert_eq!(out, 7);
+
+    assert!(try_spawn_local(async {}).is_err());
+}
+
+#[test]
+#[should_panic = "no executor is running on the current thread"]
+fn spawn_local_outside() {
+    local_runtime::block_on(async {
+        spawn_local(async {});
+    });
+}
+
+#[test]
+fn task_ids() {
+    let ex = Executor::new();
+    ex.block_on(async {
+        // The main future isn't a task
+        assert_eq!(task::try_id(), None);
+        let a = ex.spawn(async {
+            let id = task::id();
+            sleep(Duration::from_millis(5)).await;
+            // The ID is stable across polls
+            assert_eq!(task::id(), id);
+            id
+        });
+        let b = spawn_local(async { task::id() });
+        let (a_id, b_id) = (a.id(), b.id());
+        assert_ne!(a_id, b_id);
+        assert_eq!(a.await, a_id);
+        assert_eq!(b.await, b_id);
+        assert_eq!(task::try_id(), None);
+    });
+}
+
+#[test]
+fn task_poll_hooks() {
+    let polls = RefCell::new(vec![]);
+    let ex = Executor::new()
+        .before_task_poll(|id| polls.borrow_mut().push(("before", id)))
+        .after_task_poll(|id| polls.borrow_mut().push(("after", id)));
+    let id = ex.block_on(async {
+        let handle = ex.spawn(async {
+            sleep(Duration::from_millis(5)).await;
+        });
+        let id = handle.id();
+        handle.await;
+        id
+    });
+    assert_eq!(
+        *polls.borrow(),
+        [("before", id), ("after", id), ("before", id), ("after", id)]
+    );
+}
+
+#[test]
+fn run_until_stalled() {
+    let (tx, rx) = flume::unbounded();
+    let msgs = RefCell::new(vec![]);
+    let ex = Executor::new();
+    let receiver = ex.spawn(async {
+        while let Ok(msg) = rx.recv_async().await {
+            msgs.borrow_mut().push(msg);
+        }
+    });
+    let sender_tx = tx.clone();
+    ex.spawn(async move {
+        for i in 0..3 {
+            sender_tx.send(i).unwrap();
+            futures_lite::future::yield_now().await;
+        }
+    });
+    let timer = ex.spawn(sleep(Duration::from_millis(10)));
+
+    // Nothing is left to do except for the receiver and the timer, so they're left unfinished
+    assert!(ex.run_until_stalled());
+    assert_eq!(*msgs.borrow(), [0, 1, 2]);
+    assert!(!receiver.is_finished());
+    assert!(!timer.is_finished());
+    // Running again does nothing
+    assert!(!ex.try_run_one());
+    assert!(ex.run_until_stalled());
+
+    // Dropping the sender finishes the receiver
+    drop(tx);
+    assert!(ex.try_run_one());
+    assert!(receiver.is_finished());
+    assert!(!ex.try_run_one());
+    assert_eq!(ex.metrics().live_tasks(), 1);
+}
+
+#[test]
+fn graceful_shutdown() {
+    let finished = Rc::new(Cell::new(0));
+    let ex = Executor::new();
+    let start = Instant::now();
+    let (out, report) = ex.block_on_with_shutdown(
+        async {
+            for i in 1..=3 {
+                let token = ex.shutdown_token();
+                let finished = finished.clone();
+                ex.spawn(async move {
+                    token.cancelled().await;
+                    // Tasks spawned during shutdown also get drained
+                    let finished_clone = finished.clone();
+                    spawn_local(async move {
+                        let finished = finished_clone;
+                        sleep(Duration::from_millis(i)).await;
+                        finished.set(finished.get() + 1);
+                    });
+                    sleep(Duration::from_millis(i)).await;
+                    finished.set(finished.get() + 1);
+                });
+            }
+            ex.spawn(pending::<()>());
+            assert!(!ex.shutdown_token().is_cancelled());
+            5
+        },
+        Duration::from_millis(50),
+    );
+    assert_eq!(out, 5);
+    assert_eq!(finished.get(), 6);
+    assert_eq!(report.drained(), 6);
+    assert_eq!(report.cancelled(), 1);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(ex.shutdown_token().is_cancelled());
+    assert_eq!(ex.metrics().live_tasks(), 0);
+
+    // If all tasks finish, the grace period ends early
+    let ex = Executor::new();
+    let start = Instant::now();
+    let ((), report) = ex.block_on_with_shutdown(
+        async {
+            ex.spawn(sleep(Duration::from_millis(5)));
+        },
+        Duration::from_secs(10),
+    );
+    assert_eq!(report.drained(), 1);
+    assert_eq!(report.cancelled(), 0);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn shutdown_after_run() {
+    // Leftover tasks from an earlier run don't count towards the shutdown report
+    let ex = Executor::new();
+    ex.block_on(async {
+        ex.spawn(pending::<()>());
+        ex.spawn(pending::<()>());
+    });
+    let ((), report) = ex.block_on_with_shutdown(
+        async {
+            ex.spawn(sleep(Duration::from_millis(1)));
+        },
+        Duration::from_millis(50),
+    );
+    assert_eq!(report.drained(), 1);
+    assert_eq!(report.cancelled(), 0);
+}
@@ -0,0 +1,86 @@
+use std::{
+    io::SeekFrom,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use futures_lite::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, StreamExt};
+use local_runtime::{block_on, fs};
+
+fn temp_dir() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "local-runtime-fs-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn read_write() {
+    let dir = temp_dir();
+    block_on(async {
+        let path = dir.join("a.txt");
+        fs::write(&path, "hello").await.unwrap();
+        assert_eq!(fs::read(&path).await.unwrap(), b"hello");
+        assert_eq!(fs::metadata(&path).await.unwrap().len(), 5);
+
+        let new_path = dir.join("b.txt");
+        fs::rename(&path, &new_path).await.unwrap();
+        assert!(fs::metadata(&path).await.is_err());
+        assert_eq!(fs::read_to_string(&new_path).await.unwrap(), "hello");
+
+        fs::remove_file(&new_path).await.unwrap();
+        assert!(fs::read(&new_path).await.is_err());
+    });
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn file() {
+    let dir = temp_dir();
+    block_on(async {
+        let path = dir.join("file.txt");
+        let mut file = fs::File::create(&path).await.unwrap();
+        file.write_all(b"hello world").await.unwrap();
+        file.flush().await.unwrap();
+        assert_eq!(file.metadata().await.unwrap().len(), 11);
+        drop(file);
+
+        let mut file = fs::File::open(&path).await.unwrap();
+        let mut buf = [0u8; 5];
+        file.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        assert_eq!(file.seek(SeekFrom::Current(1)).await.unwrap(), 6);
+        let mut rest = String::new();
+        file.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "world");
+
+        assert_eq!(file.seek(SeekFrom::Start(0)).await.unwrap(), 0);
+        file.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    });
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn read_dir() {
+    let dir = temp_dir();
+    for i in 0..50 {
+        std::fs::write(dir.join(format!("{i}.txt")), []).unwrap();
+    }
+    let mut names = block_on(async {
+        fs::read_dir(&dir)
+            .await
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>()
+            .await
+    });
+    names.sort_unstable_by_key(|name| name.trim_end_matches(".txt").parse::<u32>().unwrap());
+    let expected: Vec<_> = (0..50).map(|i| format!("{i}.txt")).collect();
+    assert_eq!(names, expected);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "hyper")]
+
+use std::{
+    convert::Infallible,
+    net::TcpListener,
+    time::{Duration, Instant},
+};
+
+use futures_lite::future;
+use http_body_util::BodyExt;
+use hyper::{
+    body::Incoming, client, rt::Timer, server, service::service_fn, Request, Response, StatusCode,
+};
+use local_runtime::{
+    block_on,
+    hyper::{HyperExecutor, HyperIo, HyperTimer},
+    io::{duplex, Async},
+    runtime, time, Executor,
+};
+
+async fn hello(req: Request<Incoming>) -> Result<Response<String>, Infallible> {
+    let body = req.into_body().collect().await.unwrap().to_bytes();
+    Ok(Response::new(format!(
+        "hello {}",
+        String::from_utf8_lossy(&body)
+    )))
+}
+
+async fn body_string(res: Response<Incoming>) -> String {
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[test]
+fn http1_tcp() {
+    let ex = Executor::new();
+    ex.block_on(async {
+        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+        let addr = listener.get_ref().local_addr().unwrap();
+        let server = ex.spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            server::conn::http1::Builder::new()
+                .timer(HyperTimer)
+                .serve_connection(HyperIo::new(stream), service_fn(hello))
+                .await
+                .unwrap();
+        });
+
+        let stream = Async::<std::net::TcpStream>::connect(addr).await.unwrap();
+        let (mut sender, conn) = client::conn::http1::handshake(HyperIo::new(stream))
+            .await
+            .unwrap();
+        let conn = ex.spawn(conn);
+
+        for name in ["alice", "bob"] {
+            let req = Request::builder()
+                .uri("/")
+                .header("host", "localhost")
+                .body(name.to_string())
+                .unwrap();
+            let res = sender.send_request(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(body_string(res).await, format!("hello {name}"));
+        }
+
+        // Closing the client connection shuts down the server
+        drop(sender);
+        conn.await.unwrap();
+        server.await;
+    });
+}
+
+#[test]
+fn http2_duplex() {
+    let ex = Executor::new();
+    ex.block_on(async {
+        let (client_io, server_io) = duplex(1024);
+        // Both connections spawn their HTTP/2 streams onto the executor
+        let server = ex.spawn(
+            server::conn::http2::Builder::new(HyperExecutor)
+                .timer(HyperTimer)
+                .serve_connection(HyperIo::new(server_io), service_fn(hello)),
+        );
+        {
+            let (mut sender, conn) = client::conn::http2::Builder::new(HyperExecutor)
+                .timer(HyperTimer)
+                .handshake(HyperIo::new(client_io))
+                .await
+                .unwrap();
+            let conn = ex.spawn(conn);
+
+            // Requests are multiplexed over the same connection
+            let [a, b, c] = ["alice", "bob", "carol"].map(|name| {
+                let req = Request::builder()
+                    .uri("http://localhost/")
+                    .body(name.to_string())
+                    .unwrap();
+                let fut = sender.send_request(req);
+                ex.spawn(async move { body_string(fut.await.unwrap()).await })
+            });
+            assert_eq!(a.await, "hello alice");
+            assert_eq!(b.await, "hello bob");
+            assert_eq!(c.await, "hello carol");
+
+            // Dropping the sender closes the connection
+            drop(sender);
+            conn.await.unwrap();
+        }
+        server.await.unwrap();
+    });
+}
+
+#[test]
+fn header_timeout() {
+    time::pause();
+    let start = time::now();
+    let ex = Executor::new();
+    ex.block_on(async {
+        // The client never sends the request headers
+        let (_client_io, server_io) = duplex(1024);
+        let res = server::conn::http1::Builder::new()
+            .timer(HyperTimer)
+            .header_read_timeout(Duration::from_secs(30))
+            .serve_connection(HyperIo::new(server_io), service_fn(hello))
+            .await;
+        assert!(res.unwrap_err().is_timeout());
+    });
+    // With the clock paused, the timeout happens instantly
+    assert!(time::now() - start >= Duration::from_secs(30));
+}
+
+#[test]
+fn sleep_dropped_on_other_thread() {
+    let deadline = Instant::now() + Duration::from_secs(3600);
+    let mut sleep = HyperTimer.sleep_until(deadline);
+    assert!(block_on(future::poll_once(&mut sleep)).is_none());
+
+    std::thread::spawn(move || {
+        // Register a timer that looks the same as the hyper timer to the other reactor
+        let mut own = time::Timer::at(deadline);
+        assert!(block_on(future::poll_once(&mut own)).is_none());
+        // Dropping the hyper timer here mustn't cancel this thread's timer
+        drop(sleep);
+        assert_eq!(runtime::next_timer_deadline(), Some(deadline));
+    })
+    .join()
+    .unwrap();
+    // The original registration is left to expire
+    assert_eq!(runtime::next_timer_deadline(), Some(deadline));
+}
@@ -0,0 +1,123 @@
+use std::{
+    env, io,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_lite::{io::BufReader, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use local_runtime::{
+    block_on,
+    io::{duplex, stdout, MockStream},
+    Executor,
+};
+
+#[test]
+fn duplex_echo() {
+    let (mut client, mut server) = duplex(8);
+    let ex = Executor::new();
+    ex.block_on(async {
+        let echo = ex.spawn(async move {
+            let (reader, mut writer) = futures_lite::io::split(&mut server);
+            futures_lite::io::copy(reader, &mut writer).await.unwrap();
+        });
+
+        let msg: Vec<u8> = (0..100).collect();
+        let (mut reader, mut writer) = futures_lite::io::split(&mut client);
+        let write = async {
+            writer.write_all(&msg).await.unwrap();
+            writer.close().await.unwrap();
+        };
+        let read = async {
+            let mut buf = vec![];
+            reader.read_to_end(&mut buf).await.unwrap();
+            buf
+        };
+        let ((), buf) = futures_lite::future::zip(write, read).await;
+        assert_eq!(buf, msg);
+        echo.await;
+    });
+}
+
+// Reads lines from a stream and replies to each one, like a simple protocol handler
+async fn handle_lines(stream: &mut MockStream) -> io::Result<usize> {
+    let mut count = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if BufReader::new(&mut *stream).read_line(&mut line).await? == 0 {
+            return Ok(count);
+        }
+        stream
+            .write_all(format!("got {}", line.trim()).as_bytes())
+            .await?;
+        count += 1;
+    }
+}
+
+#[test]
+fn mock_stream() {
+    let mut stream = MockStream::builder()
+        .read(b"hello\n")
+        .write(b"got hello")
+        .wait(Duration::from_millis(20))
+        .read(b"bye\n")
+        .write(b"got bye")
+        .build();
+    let now = Instant::now();
+    let count = Executor::new().block_on(handle_lines(&mut stream)).unwrap();
+    assert_eq!(count, 2);
+    assert!(now.elapsed() >= Duration::from_millis(20));
+
+    let mut stream = MockStream::builder()
+        .read(b"hello\n")
+        .write_error(io::ErrorKind::ConnectionReset.into())
+        .build();
+    let err = Executor::new()
+        .block_on(handle_lines(&mut stream))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+}
+
+#[test]
+fn stdout_pipe() {
+    const LINES: usize = 10_000;
+    // The test runs itself in a child process, so that its standard output is a pipe
+    if env::var_os("STDOUT_PIPE_CHILD").is_some() {
+        // End the line that the test harness has started
+        println!();
+        block_on(async {
+            let mut out = stdout();
+            for i in 0..LINES {
+                out.write_all(format!("async {i}\n").as_bytes())
+                    .await
+                    .unwrap();
+                out.flush().await.unwrap();
+                // Mixing in synchronous writes while the handle is alive is fine, even when the
+                // pipe is full
+                println!("sync {i}");
+            }
+        });
+        return;
+    }
+
+    let child = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "stdout_pipe", "--nocapture", "--test-threads=1"])
+        .env("STDOUT_PIPE_CHILD", "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Let the pipe fill up before reading it
+    thread::sleep(Duration::from_millis(100));
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout
+        .lines()
+        .filter(|l| l.starts_with("async ") || l.starts_with("sync "))
+        .collect();
+    assert_eq!(lines.len(), LINES * 2);
+    for (i, pair) in lines.chunks(2).enumerate() {
+        assert_eq!(pair, [format!("async {i}"), format!("sync {i}")]);
+    }
+}
@@ -9,8 +9,9 @@ use std::{
 use futures_lite::{stream, StreamExt};
 
 use local_runtime::{
-    block_on, join, merge_futures, merge_streams,
+    block_on, join, join_tuple, merge_futures, merge_streams, select,
     time::{sleep, timeout, Periodic},
+    try_join,
 };
 
 struct CountFuture<'a>(&'a Cell<u8>);
@@ -172,3 +173,194 @@ fn single_entry() {
         );
     });
 }
+
+#[test]
+fn select() {
+    let count = Cell::new(0);
+    let now = Instant::now();
+    let out = block_on(async {
+        select! {
+            _ = timeout(CountFuture(&count), Duration::from_millis(50)) => None,
+            n = async {
+                let _ = timeout(CountFuture(&count), Duration::from_millis(20)).await;
+                5u8
+            } => Some(n),
+            s = async {
+                let _ = timeout(CountFuture(&count), Duration::from_millis(100)).await;
+                "string"
+            } => Some(s.len() as u8),
+        }
+    });
+    assert_eq!(out, Some(5));
+    assert!(now.elapsed() < Duration::from_millis(50));
+    // Only the completed future should be polled twice
+    assert_eq!(count.get(), 4);
+}
+
+#[test]
+fn select_disabled() {
+    block_on(async {
+        // Branches that don't match the pattern are disabled
+        let out = select! {
+            Some(x) = async { None::<u8> } => x,
+            Ok(x) = async {
+                sleep(Duration::from_millis(5)).await;
+                Ok::<_, ()>(2)
+            } => x,
+        };
+        assert_eq!(out, 2);
+
+        let out = select! {
+            _ = async { 1 }, if false => 1,
+            Err(()) = async { Ok(2) } => 2,
+            else => 0,
+        };
+        assert_eq!(out, 0);
+
+        let out = select! {
+            else => 0,
+        };
+        assert_eq!(out, 0);
+    });
+}
+
+#[test]
+fn select_block_handlers() {
+    block_on(async {
+        // Block handlers with trailing commas
+        let out = select! {
+            Some(x) = async { None::<u8> } => { x },
+            x = async { 1u8 }, if true => { x + 1 },
+            else => { 0 },
+        };
+        assert_eq!(out, 2);
+
+        // Block handlers without commas
+        let out = select! {
+            _ = async {}, if false => { 1 }
+            Some(x) = async { None::<u8> } => { x }
+            else => { 0 }
+        };
+        assert_eq!(out, 0);
+
+        // Mixed handlers
+        let out = select! {
+            _ = sleep(Duration::from_millis(50)) => { 1 },
+            x = async { 2 } => x,
+            else => 0,
+        };
+        assert_eq!(out, 2);
+    });
+}
+
+#[test]
+fn select_guards() {
+    block_on(async {
+        // The guard rejects the first output, so the other branch wins
+        let out = select! {
+            n if n > 5 = async { 3 } => n,
+            s = async {
+                sleep(Duration::from_millis(5)).await;
+                String::from("slow")
+            } => s.len() as i32,
+        };
+        assert_eq!(out, 4);
+
+        // Guards see owned bindings and can be combined with preconditions and else
+        let out = select! {
+            Some(s) if s.len() == 3 = async { Some(String::from("abc")) }, if true => s,
+            Some(s) if s.is_empty() = async { Some(String::new()) } => s,
+            else => String::from("none"),
+        };
+        assert_eq!(out, "abc");
+        let out = select! {
+            Some(s) if s.len() > 3 = async { Some(String::from("abc")) } => { s }
+            x if x != 1 && x < 5 = async { 1 } => { x.to_string() }
+            else => { String::from("none") }
+        };
+        assert_eq!(out, "none");
+    });
+}
+
+#[test]
+#[should_panic]
+fn select_no_else() {
+    block_on(async {
+        select! {
+            _ = async {}, if false => {}
+        }
+    });
+}
+
+#[test]
+fn select_loop() {
+    block_on(async {
+        let mut buf = vec![];
+        let mut periodic = Periodic::periodic(Duration::from_millis(5));
+        let mut deadline = pin!(sleep(Duration::from_millis(28)));
+        loop {
+            select! {
+                _ = periodic.next() => {
+                    buf.push(buf.len());
+                }
+                _ = deadline.as_mut() => break,
+            }
+        }
+        assert_eq!(buf, [0, 1, 2, 3, 4]);
+    });
+}
+
+#[test]
+fn join_tuple() {
+    let count = Cell::new(0);
+    let now = Instant::now();
+    let out = block_on(async {
+        join_tuple!(
+            async {
+                let _ = timeout(CountFuture(&count), Duration::from_millis(50)).await;
+                1u8
+            },
+            async {
+                let _ = timeout(CountFuture(&count), Duration::from_millis(20)).await;
+                "two"
+            },
+            // Output isn't Unpin
+            async {
+                let _ = timeout(CountFuture(&count), Duration::from_millis(100)).await;
+                std::marker::PhantomPinned
+            },
+        )
+        .await
+    });
+    assert!(now.elapsed() >= Duration::from_millis(100));
+    assert_eq!((out.0, out.1), (1, "two"));
+    // Each future should only be polled twice
+    assert_eq!(count.get(), 6);
+}
+
+#[test]
+fn try_join() {
+    block_on(async {
+        let out = try_join!(async { Ok::<_, ()>(1) }, async { Ok("two") }).await;
+        assert_eq!(out, Ok((1, "two")));
+
+        let count = Cell::new(0);
+        let now = Instant::now();
+        let out = try_join!(
+            async {
+                let _ = timeout(CountFuture(&count), Duration::from_millis(100)).await;
+                Ok(1)
+            },
+            async {
+                sleep(Duration::from_millis(10)).await;
+                Err::<(), _>("error")
+            },
+            async { Ok(()) },
+        )
+        .await;
+        assert_eq!(out, Err("error"));
+        // Return early on error
+        assert!(now.elapsed() < Duration::from_millis(100));
+        assert_eq!(count.get(), 1);
+    });
+}
@@ -0,0 +1,292 @@
+use std::{
+    cell::Cell,
+    io::{ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    os::{
+        fd::{AsRawFd, BorrowedFd},
+        unix::net::UnixStream,
+    },
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, AsyncReadExt};
+use local_runtime::{
+    block_on,
+    hooks::ParkHooks,
+    io::Async,
+    runtime::{self, Builder, NotifierBackend, Runtime, TimerBackend},
+    time::{self, sleep},
+    Executor,
+};
+use rustix::event::{poll, PollFd, PollFlags};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use rustix::process::CpuSet;
+
+#[test]
+fn builder() {
+    let (tx, rx) = flume::bounded(1);
+    let parked = Rc::new(Cell::new(false));
+    let parked_clone = parked.clone();
+    let ex = Builder::new()
+        .notifier(NotifierBackend::Pipe)
+        .timer(TimerBackend::PollTimeout)
+        .event_capacity(16)
+        .task_capacity(16)
+        .budget(1)
+        .park_hooks(ParkHooks::new().before_park(move || parked_clone.set(true)))
+        .build()
+        .unwrap();
+
+    let out = ex.block_on(async {
+        let a = ex.spawn(async {
+            sleep(Duration::from_millis(5)).await;
+            1
+        });
+        let b = ex.spawn(async { rx.recv_async().await.unwrap() });
+        // Wake up the reactor from another thread via the pipe notifier
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(2).unwrap();
+        });
+        a.await + b.await
+    });
+    assert_eq!(out, 3);
+    assert!(parked.get());
+
+    // The reactor has already been initialized
+    let err = Builder::new().build().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn builder_after_reactor_used() {
+    block_on(sleep(Duration::from_millis(1)));
+    assert!(Builder::new().build().is_err());
+}
+
+#[test]
+fn embedded_turns() {
+    let (reader, mut writer) = UnixStream::pair().unwrap();
+    let mut reader = Async::new(reader).unwrap();
+    let reader_fd = reader.get_ref().as_raw_fd();
+    let ex = Executor::new();
+    let task = ex.spawn(async move {
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).await.unwrap();
+        buf
+    });
+    let timer = ex.spawn(sleep(Duration::from_millis(20)));
+
+    // Spawned tasks are polled, but can't finish yet
+    assert!(ex.turn(Some(Duration::ZERO)).unwrap());
+    let deadline = runtime::next_timer_deadline().unwrap();
+    assert!(deadline > Instant::now());
+    let fds = runtime::poll_fds();
+    assert!(fds
+        .iter()
+        .any(|pfd| pfd.fd() == reader_fd && pfd.readable() && !pfd.writable()));
+
+    // The host loop waits on the reactor's FDs
+    writer.write_all(b"ping").unwrap();
+    // SAFETY: The FDs stay open until the end of the test
+    let borrowed: Vec<_> = fds
+        .iter()
+        .map(|pfd| unsafe { BorrowedFd::borrow_raw(pfd.fd()) })
+        .collect();
+    let mut pollfds: Vec<_> = borrowed
+        .iter()
+        .map(|fd| PollFd::new(fd, PollFlags::IN))
+        .collect();
+    assert!(poll(&mut pollfds, 1000).unwrap() > 0);
+    // Timer task is still running
+    assert!(ex.turn(Some(Duration::ZERO)).unwrap());
+    assert!(task.is_finished());
+    assert_eq!(block_on(task), *b"ping");
+
+    // Block on the reactor until the timer expires
+    let start = Instant::now();
+    assert!(!ex.turn(None).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert!(timer.is_finished());
+    assert!(runtime::next_timer_deadline().is_none());
+}
+
+#[test]
+fn reactor_turn_timeout() {
+    let start = Instant::now();
+    runtime::turn(Some(Duration::from_millis(10))).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(10));
+}
+
+#[test]
+fn paused_turn_timeout() {
+    time::pause();
+    let start = time::now();
+    let mut timer = sleep(Duration::from_secs(3600));
+    assert!(block_on(future::poll_once(&mut timer)).is_none());
+
+    // The clock only jumps as far as the turn's timeout, not all the way to the timer
+    runtime::turn(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(time::now(), start + Duration::from_millis(10));
+    runtime::turn(None).unwrap();
+    assert_eq!(time::now(), start + Duration::from_secs(3600));
+    assert!(block_on(future::poll_once(&mut timer)).is_some());
+    time::resume();
+}
+
+#[test]
+fn per_core() {
+    let results = Runtime::per_core(3, |ctx| async move {
+        let index = ctx.index();
+        assert_eq!(ctx.handles().len(), 3);
+        let task = ctx.executor().spawn(async move { index * 2 });
+        if index == 0 {
+            // Wait for a bit so that the other workers are blocked on their shutdown tokens
+            sleep(Duration::from_millis(10)).await;
+            for handle in &ctx.handles()[1..] {
+                handle.shutdown();
+            }
+        } else {
+            ctx.shutdown_token().cancelled().await;
+        }
+        task.await
+    })
+    .unwrap();
+    assert_eq!(results, [0, 2, 4]);
+}
+
+#[test]
+fn per_core_panic() {
+    let err = Runtime::new()
+        .thread_name("panicky")
+        .launch(3, |ctx| async move {
+            assert!(thread::current().name().unwrap().starts_with("panicky-"));
+            if ctx.index() == 1 {
+                sleep(Duration::from_millis(5)).await;
+                panic!("worker panic");
+            }
+            // The panicking worker shuts down the others
+            ctx.shutdown_token().cancelled().await;
+        })
+        .unwrap_err();
+    assert_eq!(err.workers().collect::<Vec<_>>(), [1]);
+    let (index, payload) = err.into_panics().pop().unwrap();
+    assert_eq!(index, 1);
+    assert_eq!(*payload.downcast::<&str>().unwrap(), "worker panic");
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn per_core_pinned() {
+    let allowed = rustix::process::sched_getaffinity(None).unwrap();
+    let cores = Runtime::new()
+        .pin_cores(true)
+        .launch(2, |_| async {
+            let set = rustix::process::sched_getaffinity(None).unwrap();
+            assert_eq!(set.count(), 1);
+            (0..CpuSet::MAX_CPU).find(|&c| set.is_set(c)).unwrap()
+        })
+        .unwrap();
+    for core in cores {
+        assert!(allowed.is_set(core));
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn reuseport_listeners() {
+    let l1 = Async::<TcpListener>::bind_reuseport(([127, 0, 0, 1], 0)).unwrap();
+    let addr = l1.get_ref().local_addr().unwrap();
+    let l2 = Async::<TcpListener>::bind_reuseport(addr).unwrap();
+    assert_eq!(l2.get_ref().local_addr().unwrap(), addr);
+    // Without SO_REUSEPORT the address is taken
+    assert!(Async::<TcpListener>::bind(addr).is_err());
+
+    block_on(async {
+        let _client = Async::<TcpStream>::connect(addr).await.unwrap();
+        futures_lite::future::or(async { l1.accept().await.unwrap().1 }, async {
+            l2.accept().await.unwrap().1
+        })
+        .await;
+    });
+}
+
+#[test]
+fn per_core_balanced() {
+    let (tx, rx) = flume::unbounded();
+    let (handle_tx, handle_rx) = flume::bounded(1);
+    let counts = Runtime::new()
+        .thread_name("worker")
+        .launch(3, |ctx| {
+            let tx = tx.clone();
+            let rx = rx.clone();
+            let handle_tx = handle_tx.clone();
+            async move {
+                if ctx.index() != 0 {
+                    ctx.shutdown_token().cancelled().await;
+                    return vec![];
+                }
+                let mut targets = vec![];
+                for _ in 0..6 {
+                    let tx = tx.clone();
+                    targets.push(ctx.spawn_balanced(move || async move {
+                        // The future runs on the worker that received the work item
+                        let name = thread::current().name().unwrap().to_owned();
+                        sleep(Duration::from_millis(10)).await;
+                        tx.send(name).unwrap();
+                    }));
+                }
+                let mut counts = vec![0; 3];
+                for _ in 0..6 {
+                    let name = rx.recv_async().await.unwrap();
+                    let index: usize = name.strip_prefix("worker-").unwrap().parse().unwrap();
+                    counts[index] += 1;
+                }
+                assert_eq!(targets, [0, 1, 2, 0, 1, 2]);
+                handle_tx.send(ctx.handles()[1].clone()).unwrap();
+                ctx.shutdown_all();
+                counts
+            }
+        })
+        .unwrap();
+    assert_eq!(counts[0], [2, 2, 2]);
+
+    // Work can't be sent to finished workers
+    let handle = handle_rx.recv().unwrap();
+    assert!(handle.spawn(|| async {}).is_err());
+}
+
+#[test]
+fn per_core_closed_on_completion() {
+    struct DropSignal(flume::Sender<()>);
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            self.0.send(()).unwrap();
+            // Hold up the worker thread while the other worker checks on it
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    let (tx, rx) = flume::bounded(1);
+    Runtime::per_core(2, |ctx| {
+        let tx = tx.clone();
+        let rx = rx.clone();
+        async move {
+            if ctx.index() == 1 {
+                // This task is dropped after the worker's future completes
+                let signal = DropSignal(tx);
+                ctx.executor().spawn(async move {
+                    let _signal = signal;
+                    future::pending::<()>().await;
+                });
+                return;
+            }
+            rx.recv_async().await.unwrap();
+            // By then, the worker no longer accepts work items
+            assert!(ctx.handles()[1].spawn(|| async {}).is_err());
+        }
+    })
+    .unwrap();
+}
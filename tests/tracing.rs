@@ -0,0 +1,91 @@
+#![cfg(feature = "tracing")]
+
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use local_runtime::{task, time::sleep, Executor};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+// Records the name of each span and the message of each event
+#[derive(Default)]
+struct Recorder {
+    next_id: AtomicU64,
+    spans: Mutex<Vec<String>>,
+    events: Mutex<Vec<String>>,
+}
+
+struct MessageVisitor<'a>(&'a mut String);
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            *self.0 = format!("{value:?}");
+        }
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        self.spans
+            .lock()
+            .unwrap()
+            .push(span.metadata().name().to_owned());
+        span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = String::new();
+        event.record(&mut MessageVisitor(&mut message));
+        self.events.lock().unwrap().push(message);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[test]
+fn task_events() {
+    let recorder = Arc::new(Recorder::default());
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let ex = Executor::new();
+        ex.block_on(async {
+            let handle = task::Builder::new().name("sleeper").spawn(&ex, async {
+                sleep(Duration::from_millis(5)).await;
+            });
+            let cancelled = ex.spawn(async {});
+            cancelled.cancel();
+            handle.await;
+        });
+    });
+
+    let spans = recorder.spans.lock().unwrap();
+    assert_eq!(spans.iter().filter(|s| *s == "task").count(), 2);
+    assert!(spans.iter().any(|s| s == "reactor.wait"));
+
+    let events = recorder.events.lock().unwrap();
+    let count = |msg: &str| events.iter().filter(|e| *e == msg).count();
+    assert_eq!(count("spawn"), 2);
+    assert_eq!(count("cancel"), 1);
+    assert_eq!(count("poll"), 2);
+    assert_eq!(count("wake"), 1);
+    assert_eq!(count("complete"), 1);
+    assert_eq!(count("timer fired"), 1);
+}